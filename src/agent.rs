@@ -28,7 +28,7 @@ pub enum AgentError {
 }
 
 /// Repräsentiert die möglichen Antwortformate eines Agenten
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum ResponseFormat {
     /// Einfacher Textstring ohne Formatierung
     Text(String),
@@ -40,6 +40,50 @@ pub enum ResponseFormat {
     Json(serde_json::Value),
 }
 
+impl ResponseFormat {
+    /// Name der Variante ("Text", "Markdown" oder "Json")
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            ResponseFormat::Text(_) => "Text",
+            ResponseFormat::Markdown(_) => "Markdown",
+            ResponseFormat::Json(_) => "Json",
+        }
+    }
+
+    /// Fügt mehrere Antworten zu einer einzigen zusammen
+    ///
+    /// Text- und Markdown-Teile werden mit `sep` verbunden (Markdown, sobald ein
+    /// Teil Markdown ist). Bestehen alle Teile aus JSON, entsteht ein JSON-Array;
+    /// in gemischten Listen werden JSON-Teile als Text eingefügt.
+    pub fn join(parts: &[ResponseFormat], sep: &str) -> ResponseFormat {
+        if !parts.is_empty() && parts.iter().all(|p| matches!(p, ResponseFormat::Json(_))) {
+            let values = parts
+                .iter()
+                .filter_map(|p| match p {
+                    ResponseFormat::Json(value) => Some(value.clone()),
+                    _ => None,
+                })
+                .collect();
+            return ResponseFormat::Json(serde_json::Value::Array(values));
+        }
+
+        let text = parts
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>()
+            .join(sep);
+
+        if parts
+            .iter()
+            .any(|p| matches!(p, ResponseFormat::Markdown(_)))
+        {
+            ResponseFormat::Markdown(text)
+        } else {
+            ResponseFormat::Text(text)
+        }
+    }
+}
+
 impl Default for ResponseFormat {
     fn default() -> Self {
         ResponseFormat::Text(String::new())
@@ -51,7 +95,7 @@ impl fmt::Display for ResponseFormat {
         match self {
             ResponseFormat::Text(text) => write!(f, "{}", text),
             ResponseFormat::Markdown(md) => write!(f, "{}", md),
-            ResponseFormat::Json(json) => write!(f, "{}", json.to_string()),
+            ResponseFormat::Json(json) => write!(f, "{}", json),
         }
     }
 }
@@ -79,6 +123,11 @@ impl From<&str> for ResponseFormat {
     }
 }
 
+/// Funktion, die Eingaben vor dem Training und der Vorhersage normalisiert
+/// (z.B. Markdown entfernen oder Kontraktionen auflösen). `Send + Sync`, damit
+/// Agenten mit Präprozessor weiterhin zwischen Threads geteilt werden können.
+pub type InputPreprocessor = Box<dyn Fn(&str) -> String + Send + Sync>;
+
 /// Training Example - Die Grundeinheit für das Training von Agenten
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct TrainingExample {
@@ -117,6 +166,74 @@ impl From<LegacyTrainingExample> for TrainingExample {
     }
 }
 
+/// Metadaten-Schlüssel für kontextabhängige Antwortvarianten, z.B.
+/// `{"variants": {"linux": "...", "windows": {"Markdown": "..."}}}`
+pub const OUTPUT_VARIANTS_KEY: &str = "variants";
+
+/// Antwort der Agenten auf leere Anfragen (nur Leerraum), sofern Trainingsdaten vorhanden
+/// sind; die Konfidenz solcher Anfragen ist 0.0
+pub const EMPTY_QUERY_RESPONSE: &str = "Empty query.";
+
+/// Metadaten-Schlüssel in `PredictionResult`, der anzeigt, dass die Eingabe wegen
+/// `max_input_chars` gekürzt wurde
+pub const INPUT_TRUNCATED_KEY: &str = "input_truncated";
+
+/// Kürzt `input` auf höchstens `max_chars` Zeichen, sofern eine Grenze gesetzt ist
+pub(crate) fn limit_input(input: &str, max_chars: Option<usize>) -> &str {
+    match max_chars {
+        Some(max_chars) => text_utils::truncate_chars(input, max_chars),
+        None => input,
+    }
+}
+
+/// Metadaten für eine Vorhersage, deren Eingabe wegen `max_chars` gekürzt wurde
+pub(crate) fn truncation_metadata(
+    input: &str,
+    max_chars: Option<usize>,
+) -> Option<serde_json::Value> {
+    (limit_input(input, max_chars).len() < input.len())
+        .then(|| serde_json::json!({ INPUT_TRUNCATED_KEY: true }))
+}
+
+impl TrainingExample {
+    /// Liefert die Antwortvariante für `tag` aus den Metadaten, falls vorhanden.
+    /// Varianten sind entweder ein `ResponseFormat` oder ein einfacher String (Text).
+    pub fn output_variant(&self, tag: &str) -> Option<ResponseFormat> {
+        let variant = self.metadata.as_ref()?.get(OUTPUT_VARIANTS_KEY)?.get(tag)?;
+        match variant {
+            serde_json::Value::String(text) => Some(ResponseFormat::Text(text.clone())),
+            other => serde_json::from_value(other.clone()).ok(),
+        }
+    }
+
+    /// Prüft, ob es sich um ein Retrieval-Beispiel handelt (Antwort = Eingabe),
+    /// wie es z.B. bei aus PDFs erzeugten Textabschnitten der Fall ist
+    pub fn is_retrieval(&self) -> bool {
+        match &self.output {
+            ResponseFormat::Text(text) | ResponseFormat::Markdown(text) => *text == self.input,
+            ResponseFormat::Json(_) => false,
+        }
+    }
+
+    /// Wandelt das Beispiel in das Legacy-Format um
+    ///
+    /// Ohne `stringify` gelingt das nur für reine Textantworten; mit `stringify`
+    /// werden Markdown- und JSON-Antworten als String übernommen.
+    pub fn to_legacy(&self, stringify: bool) -> Option<LegacyTrainingExample> {
+        let output = match &self.output {
+            ResponseFormat::Text(text) => text.clone(),
+            other if stringify => other.to_string(),
+            _ => return None,
+        };
+
+        Some(LegacyTrainingExample {
+            input: self.input.clone(),
+            output,
+            weight: self.weight,
+        })
+    }
+}
+
 /// Standardgewicht für Trainingsbeispiele
 pub fn default_weight() -> f32 {
     1.0
@@ -135,6 +252,19 @@ pub struct PredictionResult {
     pub metadata: Option<serde_json::Value>,
 }
 
+impl PredictionResult {
+    /// Wandelt das Ergebnis in ein JSON-Objekt
+    /// `{"response": ..., "confidence": ..., "metadata": ...}` um, z.B. für APIs. Die Antwort
+    /// wird mit ihrer Variante serialisiert (`{"Text": "..."}`), fehlende Metadaten als `null`.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "response": self.response,
+            "confidence": self.confidence,
+            "metadata": self.metadata,
+        })
+    }
+}
+
 impl From<ResponseFormat> for PredictionResult {
     fn from(response: ResponseFormat) -> Self {
         Self {
@@ -151,6 +281,14 @@ impl From<PredictionResult> for ResponseFormat {
     }
 }
 
+/// Standard-Konfidenzschwelle, ab der ein Agent eine Eingabe beantworten kann
+pub const DEFAULT_CONFIDENCE_THRESHOLD: f32 = 0.5;
+
+/// Konfidenz, die die Standardimplementierung von `Agent::confidence` für eine nicht
+/// abgestufte Antwort meldet. Sie liegt auf dem Standard-Schwellenwert, sodass
+/// `can_answer` solchen Antworten erst vertraut, wenn der Agent sie bewertet.
+pub const DEFAULT_CONFIDENCE_CEILING: f32 = 0.5;
+
 /// Haupttrait für alle Agenten - definiert die grundlegende Funktionalität
 pub trait Agent {
     /// Verarbeitet eine Eingabe und gibt eine passende Antwort zurück
@@ -166,25 +304,55 @@ pub trait Agent {
     }
 
     /// Bestimmt die Konfidenz des Agenten für eine bestimmte Eingabe (0.0 - 1.0)
+    ///
+    /// Die Standardimplementierung kann Treffer nicht bewerten: Sie meldet
+    /// `confidence_floor` ohne Treffer und `confidence_ceiling` für jede Antwort.
+    /// Agenten mit abgestuften Treffern sollten sie überschreiben.
     fn confidence(&self, input: &str) -> f32 {
         let response = self.predict(input);
         match response {
-            ResponseFormat::Text(ref s) | ResponseFormat::Markdown(ref s) => {
+            ResponseFormat::Text(ref s) | ResponseFormat::Markdown(ref s)
                 if s.contains("No matching answer found")
                     || s.contains("No training data available")
-                {
-                    0.0
-                } else {
-                    1.0
-                }
+                    || s == EMPTY_QUERY_RESPONSE =>
+            {
+                self.confidence_floor()
             }
-            _ => 1.0,
+            _ => self.confidence_ceiling(),
         }
     }
 
+    /// Konfidenz der Standardimplementierung von `confidence`, wenn es keine Antwort gibt
+    fn confidence_floor(&self) -> f32 {
+        0.0
+    }
+
+    /// Konfidenz der Standardimplementierung von `confidence` für eine Antwort.
+    /// Agenten, deren Antworten immer verlässlich sind, können sie anheben.
+    fn confidence_ceiling(&self) -> f32 {
+        DEFAULT_CONFIDENCE_CEILING
+    }
+
+    /// Liefert die Eingaben (Fragen), auf die der Agent trainiert wurde, z.B. für
+    /// Autovervollständigung. Die Standardimplementierung kennt keine Eingaben.
+    fn known_inputs(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// Liefert die Antwortformate, die der Agent zurückgeben kann, z.B. damit eine
+    /// Oberfläche passende Renderer einrichtet. Die Standardimplementierung kennt nur Text.
+    fn supported_formats(&self) -> Vec<&'static str> {
+        vec!["Text"]
+    }
+
+    /// Konfidenz, die eine Antwort überschreiten muss, damit `can_answer` sie akzeptiert
+    fn confidence_threshold(&self) -> f32 {
+        DEFAULT_CONFIDENCE_THRESHOLD
+    }
+
     /// Prüft, ob der Agent die Eingabe beantworten kann
     fn can_answer(&self, input: &str) -> bool {
-        self.confidence(input) > 0.5
+        self.confidence(input) > self.confidence_threshold()
     }
 
     /// Hilfsmethode für Rückwärtskompatibilität
@@ -193,6 +361,20 @@ pub trait Agent {
     }
 }
 
+/// Ermittelt die Antwortformate einer Menge von Beispielen. "Text" ist immer enthalten,
+/// da Agenten ohne Treffer mit einer Textantwort reagieren.
+pub(crate) fn supported_formats_of(examples: &[TrainingExample]) -> Vec<&'static str> {
+    ["Text", "Markdown", "Json"]
+        .into_iter()
+        .filter(|&name| {
+            name == "Text"
+                || examples
+                    .iter()
+                    .any(|example| example.output.variant_name() == name)
+        })
+        .collect()
+}
+
 /// Trait für Agenten, die mit Beispielen trainiert werden können
 pub trait TrainableAgent: Agent {
     /// Trainiert den Agenten mit einer Liste von Beispielen
@@ -200,7 +382,7 @@ pub trait TrainableAgent: Agent {
 
     /// Trainiert mit einem einzelnen Beispiel
     fn train_single(&mut self, example: &TrainingExample) {
-        self.train(&[example.clone()]);
+        self.train(std::slice::from_ref(example));
     }
 
     /// Hilfsmethode für das Training mit Legacy-Daten
@@ -217,7 +399,13 @@ pub trait TrainableAgent: Agent {
 
         self.train(&converted);
     }
+}
 
+/// Generische Hilfsmethoden für trainierbare Agenten.
+///
+/// Sie liegen in einem eigenen Trait, damit `TrainableAgent` als Trait-Objekt
+/// (`Box<dyn TrainableAgent>`) nutzbar bleibt; sie stehen auch dort zur Verfügung.
+pub trait TrainableAgentExt: TrainableAgent {
     /// Fügt ein neues Trainingsbeispiel hinzu und trainiert den Agenten
     fn add_example(&mut self, input: &str, output: impl Into<ResponseFormat>, weight: f32) {
         let example = TrainingExample {
@@ -230,6 +418,8 @@ pub trait TrainableAgent: Agent {
     }
 }
 
+impl<T: TrainableAgent + ?Sized> TrainableAgentExt for T {}
+
 /// Trait für Agenten, die Kontextinformationen nutzen können
 pub trait ContextualAgent: Agent {
     /// Fügt eine Frage-Antwort-Paar zum Kontext hinzu
@@ -240,6 +430,13 @@ pub trait ContextualAgent: Agent {
         self.add_context(question, ResponseFormat::Text(answer));
     }
 
+    /// Fügt ein gewichtetes Frage-Antwort-Paar hinzu; `add_context` entspricht dem Gewicht 1.0.
+    /// Die Standardimplementierung ignoriert das Gewicht.
+    fn add_context_weighted(&mut self, question: String, answer: ResponseFormat, weight: f32) {
+        let _ = weight;
+        self.add_context(question, answer);
+    }
+
     /// Leert den Kontext
     fn clear_context(&mut self);
 }
@@ -285,8 +482,13 @@ pub mod text_utils {
 
     /// Tokenisiert Text in einzelne Wörter
     pub fn tokenize(text: &str) -> Vec<String> {
-        text.to_lowercase()
-            .chars()
+        tokenize_preserving_case(&text.to_lowercase())
+    }
+
+    /// Tokenisiert Text in einzelne Wörter, ohne die Groß-/Kleinschreibung zu ändern
+    /// (z.B. damit "US" und "us" unterscheidbar bleiben)
+    pub fn tokenize_preserving_case(text: &str) -> Vec<String> {
+        text.chars()
             .filter(|&c| c.is_alphabetic() || c.is_whitespace())
             .collect::<String>()
             .split_whitespace()
@@ -301,10 +503,7 @@ pub mod text_utils {
 
     /// Entfernt Stoppwörter aus einer Liste von Tokens
     pub fn remove_stopwords(tokens: Vec<String>, lang: &str) -> Vec<String> {
-        let stopwords = match lang.to_lowercase().as_str() {
-            "de" | "deu" | "german" => &STOPWORDS_DE,
-            _ => &STOPWORDS_EN, // Standardmäßig Englisch
-        };
+        let stopwords = stopwords_for(lang);
 
         tokens
             .into_iter()
@@ -312,6 +511,18 @@ pub mod text_utils {
             .collect()
     }
 
+    /// Prüft, ob ein Token (unabhängig von Groß-/Kleinschreibung) ein Stoppwort ist
+    pub fn is_stopword(token: &str, lang: &str) -> bool {
+        stopwords_for(lang).contains(token.to_lowercase().as_str())
+    }
+
+    fn stopwords_for(lang: &str) -> &'static HashSet<&'static str> {
+        match lang.to_lowercase().as_str() {
+            "de" | "deu" | "german" => &STOPWORDS_DE,
+            _ => &STOPWORDS_EN, // Standardmäßig Englisch
+        }
+    }
+
     /// Berechnet Levenshtein-Distanz zwischen zwei Strings
     pub fn levenshtein_distance(a: &str, b: &str) -> usize {
         if a.is_empty() {
@@ -348,6 +559,31 @@ pub mod text_utils {
         cache[b_len]
     }
 
+    /// Berechnet die Damerau-Levenshtein-Distanz: wie Levenshtein, zusätzlich zählt das
+    /// Vertauschen zweier benachbarter Zeichen ("teh" -> "the") als ein Schritt. Anders als
+    /// bei "optimal string alignment" darf ein vertauschtes Paar weiter bearbeitet werden
+    /// ("ca" -> "abc" kostet 2 statt 3).
+    pub fn damerau_levenshtein_distance(a: &str, b: &str) -> usize {
+        strsim::damerau_levenshtein(a, b)
+    }
+
+    /// Levenshtein-Distanz geteilt durch die Zeichenlänge des längeren Strings (0.0 - 1.0).
+    /// Zwei leere Strings haben die Distanz 0.0.
+    pub fn normalized_levenshtein(a: &str, b: &str) -> f32 {
+        normalized_distance(a, b, levenshtein_distance)
+    }
+
+    /// Teilt eine beliebige Editierdistanz durch die Zeichenlänge des längeren Strings
+    /// (0.0 - 1.0). Zwei leere Strings haben die Distanz 0.0.
+    pub fn normalized_distance(a: &str, b: &str, distance: impl Fn(&str, &str) -> usize) -> f32 {
+        let max_len = a.chars().count().max(b.chars().count());
+        if max_len == 0 {
+            return 0.0;
+        }
+
+        distance(a, b) as f32 / max_len as f32
+    }
+
     /// Berechnet die Jaccard-Ähnlichkeit zwischen zwei Strings
     pub fn jaccard_similarity(a: &str, b: &str) -> f32 {
         let set_a: HashSet<_> = tokenize(a).into_iter().collect();
@@ -379,6 +615,47 @@ pub mod text_utils {
             .collect()
     }
 
+    /// Kürzt einen String auf höchstens `n` Zeichen, ohne ein Zeichen zu zerteilen
+    pub fn truncate_chars(s: &str, n: usize) -> &str {
+        match s.char_indices().nth(n) {
+            Some((byte_idx, _)) => &s[..byte_idx],
+            None => s,
+        }
+    }
+
+    /// Kürzt einen String auf höchstens `n` Zeichen und markiert Kürzungen mit "…"
+    pub fn truncate_chars_with_ellipsis(s: &str, n: usize) -> String {
+        if s.chars().count() <= n {
+            return s.to_string();
+        }
+        if n == 0 {
+            return String::new();
+        }
+
+        // Das Auslassungszeichen zählt zur Länge
+        format!("{}…", truncate_chars(s, n - 1))
+    }
+
+    /// Erstellt Wort-N-Gramme aus einer Liste von Tokens (durch Leerzeichen verbunden)
+    pub fn word_ngrams(tokens: &[String], n: usize) -> Vec<String> {
+        if n == 0 || tokens.len() < n {
+            return Vec::new();
+        }
+
+        tokens.windows(n).map(|window| window.join(" ")).collect()
+    }
+
+    /// Berechnet einen stabilen 64-Bit-Hash (FNV-1a), der sich zwischen
+    /// Programmläufen und Rust-Versionen nicht ändert
+    pub fn stable_hash(text: &str) -> u64 {
+        const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+        const FNV_PRIME: u64 = 0x100000001b3;
+
+        text.bytes().fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        })
+    }
+
     /// Normalisiert Text für verschiedene Verarbeitungsschritte
     pub fn normalize_text(text: &str) -> String {
         text.to_lowercase()
@@ -387,12 +664,101 @@ pub mod text_utils {
             .trim()
             .to_string()
     }
+
+    /// Mindestlänge (in Zeichen), ab der `dedup_overlap` ein gemeinsames Ende/Anfang
+    /// als echte Überlappung wertet. Kürzere Treffer sind meist Zufall
+    /// (z.B. "the ca**t**" / "**t**iger").
+    pub const MIN_OVERLAP_CHARS: usize = 8;
+
+    /// Fügt zwei überlappende Textabschnitte (z.B. benachbarte PDF-Chunks) zusammen,
+    /// wobei das längste Ende von `a`, mit dem `b` beginnt, nur einmal übernommen wird.
+    /// Überlappungen kürzer als [`MIN_OVERLAP_CHARS`] werden ignoriert; ohne Überlappung
+    /// werden die Abschnitte mit einem Leerzeichen verbunden.
+    pub fn dedup_overlap(a: &str, b: &str) -> String {
+        // Byte-Länge des kürzesten zulässigen Präfixes von `b`
+        let min_len = b
+            .char_indices()
+            .nth(MIN_OVERLAP_CHARS.saturating_sub(1))
+            .map(|(idx, c)| idx + c.len_utf8());
+
+        // Mögliche Überlappungen sind Präfixe von `b`, die an Zeichengrenzen enden
+        let overlap = min_len.and_then(|min_len| {
+            b.char_indices()
+                .rev()
+                .map(|(idx, c)| idx + c.len_utf8())
+                .take_while(|&len| len >= min_len)
+                .find(|&len| len <= a.len() && a.ends_with(&b[..len]))
+        });
+
+        match overlap {
+            Some(len) => format!("{}{}", a, &b[len..]),
+            None if a.is_empty() || b.is_empty() => format!("{}{}", a, b),
+            None => format!("{} {}", a, b),
+        }
+    }
+
+    /// Vereinheitlicht typografische Zeichen und Leerraum: typografische Anführungszeichen
+    /// werden zu geraden, Gedankenstriche zu '-', Leerraumfolgen zu einem Leerzeichen,
+    /// Leerraum am Rand entfällt
+    pub fn normalize_punctuation(text: &str) -> String {
+        text.split_whitespace()
+            .map(|word| {
+                word.chars()
+                    .map(|c| match c {
+                        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => '\'',
+                        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => '"',
+                        '\u{2010}'..='\u{2015}' | '\u{2212}' => '-',
+                        _ => c,
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Minimal agent relying on the default `confidence`: answers only questions with a '?'
+    struct QuestionAgent {
+        ceiling: Option<f32>,
+    }
+
+    impl Agent for QuestionAgent {
+        fn predict(&self, input: &str) -> ResponseFormat {
+            if input.contains('?') {
+                ResponseFormat::Text("Maybe".to_string())
+            } else {
+                ResponseFormat::Text("No matching answer found.".to_string())
+            }
+        }
+
+        fn confidence_ceiling(&self) -> f32 {
+            self.ceiling.unwrap_or(DEFAULT_CONFIDENCE_CEILING)
+        }
+    }
+
+    #[test]
+    fn test_default_confidence_is_not_blanket() {
+        let agent = QuestionAgent { ceiling: None };
+        assert_eq!(agent.confidence("anything?"), DEFAULT_CONFIDENCE_CEILING);
+        assert!(agent.confidence("anything?") < 1.0);
+        assert_eq!(agent.confidence("anything"), 0.0);
+        // Ungraded answers do not pass the default threshold
+        assert!(!agent.can_answer("anything?"));
+        assert_eq!(
+            agent.predict_with_metadata("anything?").confidence,
+            DEFAULT_CONFIDENCE_CEILING
+        );
+
+        let trusted = QuestionAgent { ceiling: Some(0.9) };
+        assert_eq!(trusted.confidence("anything?"), 0.9);
+        assert!(trusted.can_answer("anything?"));
+        assert!(!trusted.can_answer("anything"));
+    }
+
     #[test]
     fn test_response_format_conversion() {
         let text = ResponseFormat::Text("Hello".to_string());
@@ -404,6 +770,118 @@ mod tests {
         assert_eq!(string, r#"{"key":"value"}"#);
     }
 
+    #[test]
+    fn test_dedup_overlap() {
+        let shared: String = (0..20).map(|i| format!("Satz {:03}. ", i)).collect();
+        assert_eq!(shared.chars().count(), 200);
+        let a = format!("Anfang des ersten Abschnitts. {}", shared);
+        let b = format!("{}Ende des zweiten Abschnitts.", shared);
+
+        let stitched = text_utils::dedup_overlap(&a, &b);
+        assert_eq!(
+            stitched,
+            format!(
+                "Anfang des ersten Abschnitts. {}Ende des zweiten Abschnitts.",
+                shared
+            )
+        );
+        assert_eq!(stitched.matches("Satz 007.").count(), 1);
+
+        // Multi-byte characters at the boundary
+        assert_eq!(
+            text_utils::dedup_overlap("Grüße aus Köln am Rhein", "Köln am Rhein und Düsseldorf"),
+            "Grüße aus Köln am Rhein und Düsseldorf"
+        );
+        // Ein einzelnes gemeinsames Zeichen ist keine Überlappung
+        assert_eq!(
+            text_utils::dedup_overlap("the cat", "tiger"),
+            "the cat tiger"
+        );
+        assert_eq!(
+            text_utils::dedup_overlap("Grüße aus Köln", "Köln am Rhein"),
+            "Grüße aus Köln Köln am Rhein"
+        );
+        assert_eq!(
+            text_utils::dedup_overlap("ohne", "Überlappung"),
+            "ohne Überlappung"
+        );
+        assert_eq!(text_utils::dedup_overlap("", "b"), "b");
+    }
+
+    #[test]
+    fn test_prediction_result_to_json() {
+        let text = PredictionResult {
+            response: ResponseFormat::Text("Hello".to_string()),
+            confidence: 0.5,
+            metadata: None,
+        };
+        assert_eq!(
+            text.to_json(),
+            serde_json::json!({"response": {"Text": "Hello"}, "confidence": 0.5, "metadata": null})
+        );
+
+        let markdown = PredictionResult {
+            response: ResponseFormat::Markdown("**Hi**".to_string()),
+            confidence: 1.0,
+            metadata: Some(serde_json::json!({"example_index": 2})),
+        };
+        assert_eq!(
+            markdown.to_json(),
+            serde_json::json!({
+                "response": {"Markdown": "**Hi**"},
+                "confidence": 1.0,
+                "metadata": {"example_index": 2}
+            })
+        );
+
+        let json = PredictionResult::from(ResponseFormat::Json(serde_json::json!({"ok": true})));
+        assert_eq!(
+            json.to_json(),
+            serde_json::json!({"response": {"Json": {"ok": true}}, "confidence": 1.0, "metadata": null})
+        );
+    }
+
+    #[test]
+    fn test_response_format_join_text() {
+        let parts = vec![
+            ResponseFormat::Text("first".to_string()),
+            ResponseFormat::Text("second".to_string()),
+        ];
+        match ResponseFormat::join(&parts, " | ") {
+            ResponseFormat::Text(text) => assert_eq!(text, "first | second"),
+            other => panic!("Expected text, got {:?}", other),
+        }
+
+        let mixed = vec![
+            ResponseFormat::Text("plain".to_string()),
+            ResponseFormat::Markdown("**bold**".to_string()),
+        ];
+        match ResponseFormat::join(&mixed, "\n") {
+            ResponseFormat::Markdown(md) => assert_eq!(md, "plain\n**bold**"),
+            other => panic!("Expected markdown, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_response_format_join_json() {
+        let parts = vec![
+            ResponseFormat::Json(serde_json::json!({"a": 1})),
+            ResponseFormat::Json(serde_json::json!({"b": 2})),
+        ];
+        match ResponseFormat::join(&parts, ", ") {
+            ResponseFormat::Json(value) => {
+                assert_eq!(value, serde_json::json!([{"a": 1}, {"b": 2}]))
+            }
+            other => panic!("Expected JSON, got {:?}", other),
+        }
+
+        let mixed = vec![
+            ResponseFormat::Text("value:".to_string()),
+            ResponseFormat::Json(serde_json::json!(42)),
+        ];
+        assert_eq!(String::from(ResponseFormat::join(&mixed, " ")), "value: 42");
+    }
+
     #[test]
     fn test_legacy_conversion() {
         let legacy = LegacyTrainingExample {
@@ -418,6 +896,47 @@ mod tests {
         assert_eq!(modern.weight, 2.0);
     }
 
+    #[test]
+    fn test_is_retrieval() {
+        let chunk = TrainingExample {
+            input: "A chunk of text.".to_string(),
+            output: ResponseFormat::Text("A chunk of text.".to_string()),
+            weight: 1.0,
+            metadata: None,
+        };
+        assert!(chunk.is_retrieval());
+
+        let qa = TrainingExample {
+            output: ResponseFormat::Text("An answer.".to_string()),
+            ..chunk
+        };
+        assert!(!qa.is_retrieval());
+    }
+
+    #[test]
+    fn test_to_legacy() {
+        let text = TrainingExample {
+            input: "hello".to_string(),
+            output: ResponseFormat::Text("world".to_string()),
+            weight: 2.0,
+            metadata: Some(serde_json::json!({"source": "test"})),
+        };
+        let legacy = text.to_legacy(false).unwrap();
+        let round_trip: TrainingExample = legacy.into();
+        assert_eq!(round_trip.input, "hello");
+        assert_eq!(String::from(round_trip.output), "world");
+        assert_eq!(round_trip.weight, 2.0);
+
+        let json = TrainingExample {
+            input: "data".to_string(),
+            output: ResponseFormat::Json(serde_json::json!({"key": "value"})),
+            weight: 1.0,
+            metadata: None,
+        };
+        assert!(json.to_legacy(false).is_none());
+        assert_eq!(json.to_legacy(true).unwrap().output, r#"{"key":"value"}"#);
+    }
+
     #[test]
     fn test_text_utils() {
         let tokens = text_utils::tokenize("Hello, world! How are you?");
@@ -434,4 +953,126 @@ mod tests {
         let similarity = text_utils::jaccard_similarity("hello world", "world hello");
         assert_eq!(similarity, 1.0);
     }
+
+    #[test]
+    fn test_trainable_agent_is_object_safe() {
+        use crate::match_agent::MatchAgent;
+        use crate::tfidf_agent::TfidfAgent;
+
+        let mut agents: Vec<Box<dyn TrainableAgent>> = vec![
+            Box::new(TfidfAgent::new()),
+            Box::new(MatchAgent::new_exact()),
+        ];
+        let data = [TrainingExample {
+            input: "what is rust".to_string(),
+            output: ResponseFormat::Text("A language".to_string()),
+            weight: 1.0,
+            metadata: None,
+        }];
+
+        for agent in agents.iter_mut() {
+            agent.train(&data);
+            assert_eq!(String::from(agent.predict("what is rust")), "A language");
+
+            // The generic helpers work on trait objects as well
+            agent.add_example("what is cargo", "A build tool", 1.0);
+            assert_eq!(String::from(agent.predict("what is cargo")), "A build tool");
+        }
+    }
+
+    #[test]
+    fn test_json_answer_key_order_is_stable() {
+        let raw = r#"{"zeta": 1, "alpha": {"y": 2, "b": 3}, "mid": [1, 2]}"#;
+        let answer = ResponseFormat::Json(serde_json::from_str(raw).unwrap());
+
+        let first = serde_json::to_string(&answer).unwrap();
+        let reloaded: ResponseFormat = serde_json::from_str(&first).unwrap();
+        assert_eq!(serde_json::to_string(&reloaded).unwrap(), first);
+        assert_eq!(answer.to_string(), reloaded.to_string());
+
+        let expected = if cfg!(feature = "preserve_order") {
+            r#"{"zeta":1,"alpha":{"y":2,"b":3},"mid":[1,2]}"#
+        } else {
+            r#"{"alpha":{"b":3,"y":2},"mid":[1,2],"zeta":1}"#
+        };
+        assert_eq!(answer.to_string(), expected);
+        assert_eq!(String::from(answer), expected);
+    }
+
+    #[test]
+    fn test_normalized_levenshtein() {
+        assert!((text_utils::normalized_levenshtein("kitten", "sitting") - 3.0 / 7.0).abs() < 1e-6);
+        assert_eq!(text_utils::normalized_levenshtein("", ""), 0.0);
+        assert_eq!(text_utils::normalized_levenshtein("abc", ""), 1.0);
+        assert_eq!(text_utils::normalized_levenshtein("same", "same"), 0.0);
+        // Counts characters, not bytes
+        assert_eq!(text_utils::normalized_levenshtein("über", "uber"), 0.25);
+        assert_eq!(
+            text_utils::normalized_distance("teh", "the", text_utils::damerau_levenshtein_distance),
+            1.0 / 3.0
+        );
+    }
+
+    #[test]
+    fn test_damerau_levenshtein_distance() {
+        assert_eq!(text_utils::levenshtein_distance("teh", "the"), 2);
+        assert_eq!(text_utils::damerau_levenshtein_distance("teh", "the"), 1);
+        assert_eq!(
+            text_utils::damerau_levenshtein_distance("kitten", "sitting"),
+            3
+        );
+        assert_eq!(text_utils::damerau_levenshtein_distance("", "abc"), 3);
+        assert_eq!(text_utils::damerau_levenshtein_distance("abc", ""), 3);
+        assert_eq!(text_utils::damerau_levenshtein_distance("same", "same"), 0);
+        // Unlike optimal string alignment, a transposed pair can be edited again
+        assert_eq!(text_utils::damerau_levenshtein_distance("ca", "abc"), 2);
+        assert_eq!(strsim::osa_distance("ca", "abc"), 3);
+        assert_eq!(text_utils::damerau_levenshtein_distance("abcd", "acbd"), 1);
+        // Counts characters, not bytes
+        assert_eq!(text_utils::damerau_levenshtein_distance("über", "übre"), 1);
+    }
+
+    #[test]
+    fn test_tokenize_preserving_case() {
+        let tokens = text_utils::tokenize_preserving_case("NASA and nasa, US and us!");
+        assert_eq!(tokens, vec!["NASA", "and", "nasa", "US", "and", "us"]);
+        assert_ne!(tokens[0], tokens[2]);
+
+        // The default tokenizer still lowercases
+        assert_eq!(text_utils::tokenize("NASA"), vec!["nasa"]);
+    }
+
+    #[test]
+    fn test_stable_hash() {
+        // Reference values of 64-bit FNV-1a
+        assert_eq!(text_utils::stable_hash(""), 0xcbf29ce484222325);
+        assert_eq!(text_utils::stable_hash("a"), 0xaf63dc4c8601ec8c);
+        assert_ne!(text_utils::stable_hash("ab"), text_utils::stable_hash("ba"));
+    }
+
+    #[test]
+    fn test_word_ngrams() {
+        let tokens = text_utils::tokenize("machine learning is fun");
+        assert_eq!(
+            text_utils::word_ngrams(&tokens, 2),
+            vec!["machine learning", "learning is", "is fun"]
+        );
+        assert!(text_utils::word_ngrams(&tokens, 5).is_empty());
+        assert!(text_utils::word_ngrams(&tokens, 0).is_empty());
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(text_utils::truncate_chars("héllo wörld", 4), "héll");
+        assert_eq!(text_utils::truncate_chars("🦀🦀🦀", 2), "🦀🦀");
+        assert_eq!(text_utils::truncate_chars("short", 10), "short");
+        assert_eq!(text_utils::truncate_chars("abc", 0), "");
+
+        let truncated = text_utils::truncate_chars_with_ellipsis("crème brûlée 🍮", 8);
+        assert_eq!(truncated, "crème b…");
+        assert_eq!(truncated.chars().count(), 8);
+        assert_eq!(text_utils::truncate_chars_with_ellipsis("🦀🦀", 2), "🦀🦀");
+        assert_eq!(text_utils::truncate_chars_with_ellipsis("🦀🦀🦀", 2), "🦀…");
+        assert_eq!(text_utils::truncate_chars_with_ellipsis("🦀🦀🦀", 0), "");
+    }
 }
@@ -1,29 +1,38 @@
 // src/bin/airust.rs - Corrected unified CLI tool
-use airust::agent::{Agent, ContextualAgent, ResponseFormat, TrainableAgent, TrainingExample};
-use airust::context_agent::ContextAgent;
+use airust::agent::{
+    Agent, ContextualAgent, PredictionResult, ResponseFormat, TrainableAgent, TrainingExample,
+};
+use airust::context_agent::{ContextAgent, ContextFormat};
 use airust::knowledge::KnowledgeBase;
 use airust::match_agent::MatchAgent;
 use airust::tfidf_agent::TfidfAgent;
 use std::env;
+use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
 
 enum Command {
     Query(String),
-    Interactive,
+    Interactive(Option<PathBuf>),
     Knowledge,
     Help,
 }
 
+/// Answers below this confidence are followed by a "Did you mean" suggestion
+const SUGGESTION_CONFIDENCE: f32 = 0.3;
+
 enum AgentType {
     Simple,
     Fuzzy,
-    TFIDF,
+    Tfidf,
     Context,
 }
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    // --quiet may appear anywhere and hides the confidence next to answers
+    let quiet = env::args().any(|arg| arg == "--quiet");
+    let args: Vec<String> = env::args().filter(|arg| arg != "--quiet").collect();
 
     if args.len() < 2 {
         print_help();
@@ -40,7 +49,7 @@ fn main() {
             let _agent_type = match args[2].as_str() {
                 "simple" => AgentType::Simple,
                 "fuzzy" => AgentType::Fuzzy,
-                "tfidf" => AgentType::TFIDF,
+                "tfidf" => AgentType::Tfidf,
                 "context" => AgentType::Context,
                 _ => {
                     eprintln!("Unknown agent type: {}", args[2]);
@@ -52,7 +61,15 @@ fn main() {
             let question = args[3..].join(" ");
             Command::Query(question)
         }
-        "interactive" => Command::Interactive,
+        "interactive" => Command::Interactive(args.get(2).map(PathBuf::from)),
+        "knowledge" if args.get(2).map(String::as_str) == Some("add") => {
+            handle_knowledge_add(&args[3..]);
+            return;
+        }
+        "knowledge" if args.get(2).map(String::as_str) == Some("dump") => {
+            handle_knowledge_dump(&args[3..]);
+            return;
+        }
         "knowledge" => Command::Knowledge,
         "help" => Command::Help,
         _ => {
@@ -63,9 +80,9 @@ fn main() {
     };
 
     match command {
-        Command::Query(question) => handle_query(&args[2], &question),
-        Command::Interactive => run_interactive_mode(),
-        Command::Knowledge => run_knowledge_management(),
+        Command::Query(question) => handle_query(&args[2], &question, quiet),
+        Command::Interactive(path) => run_interactive_mode(path, quiet),
+        Command::Knowledge => run_knowledge_management(quiet),
         Command::Help => print_help(),
     }
 }
@@ -75,10 +92,18 @@ fn print_help() {
     println!();
     println!("Usage:");
     println!("  airust query <agent> <question>   - Ask a question to an agent");
-    println!("  airust interactive             - Start interactive mode");
+    println!("  airust interactive [file]      - Start interactive mode, optionally with a");
+    println!("                                   knowledge file instead of the embedded data");
     println!("  airust knowledge               - Knowledge base management");
+    println!("  airust knowledge add --input <q> --output <a> [--weight <w>]");
+    println!("                                 - Add an example to knowledge/train.json");
+    println!("  airust knowledge dump <path>   - Export the embedded training data to a file");
     println!("  airust help                    - Show this help");
     println!();
+    println!("Options:");
+    println!("  --quiet  - Hide the confidence next to answers of query, interactive and");
+    println!("             knowledge test mode");
+    println!();
     println!("Available agents:");
     println!("  simple   - Exact match");
     println!("  fuzzy    - Approximate match (Levenshtein)");
@@ -86,42 +111,162 @@ fn print_help() {
     println!("  context  - Considers previous conversation");
 }
 
-fn handle_query(agent_type: &str, question: &str) {
+/// Prints an answer, preceded by its confidence unless `quiet` is set
+fn print_answer(result: &PredictionResult, quiet: bool) {
+    if quiet {
+        println!("Answer: {}", result.response);
+    } else {
+        println!(
+            "Answer (confidence {:.2}): {}",
+            result.confidence, result.response
+        );
+    }
+}
+
+fn handle_query(agent_type: &str, question: &str, quiet: bool) {
     // Loads knowledge base
     let kb = KnowledgeBase::from_embedded();
     let examples = kb.get_examples();
 
     // Creates matching agent
-    let answer = match agent_type {
+    let result = match agent_type {
         "simple" => {
             let mut agent = MatchAgent::new_exact();
             agent.train(examples);
-            agent.predict(question)
+            agent.predict_with_metadata(question)
         }
         "fuzzy" => {
             let mut agent = MatchAgent::new_fuzzy();
             agent.train(examples);
-            agent.predict(question)
+            agent.predict_with_metadata(question)
         }
         "tfidf" => {
             let mut agent = TfidfAgent::new();
             agent.train(examples);
-            agent.predict(question)
+            agent.predict_with_metadata(question)
         }
         "context" => {
             // In non-interactive mode there is no context
             let mut base_agent = TfidfAgent::new();
             base_agent.train(examples);
             let agent = ContextAgent::new(base_agent, 3);
-            agent.predict(question)
+            agent.predict_with_metadata(question)
+        }
+        _ => {
+            println!("Answer: Unknown agent type: {}", agent_type);
+            return;
         }
-        _ => ResponseFormat::Text(format!("Unknown agent type: {}", agent_type)),
     };
 
-    println!("Answer: {}", String::from(answer));
+    print_answer(&result, quiet);
 }
 
-fn run_interactive_mode() {
+fn handle_knowledge_add(args: &[String]) {
+    let mut input = None;
+    let mut output = None;
+    let mut weight = 1.0;
+
+    // Parse arguments
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1).cloned();
+        match (args[i].as_str(), value) {
+            ("--input", Some(value)) => input = Some(value),
+            ("--output", Some(value)) => output = Some(value),
+            ("--weight", Some(value)) => match value.parse::<f32>() {
+                Ok(value) => weight = value,
+                Err(_) => {
+                    eprintln!("Error: --weight requires a decimal number");
+                    process::exit(1);
+                }
+            },
+            (option, _) => {
+                eprintln!("Error: Unknown or incomplete option: {}", option);
+                print_help();
+                process::exit(1);
+            }
+        }
+        i += 2;
+    }
+
+    let (input, output) = match (input, output) {
+        (Some(input), Some(output)) => (input, output),
+        _ => {
+            eprintln!("Error: 'knowledge add' requires --input and --output");
+            process::exit(1);
+        }
+    };
+
+    // Validate the embedded training file
+    let path = PathBuf::from("knowledge").join("train.json");
+    match fs::metadata(&path) {
+        Ok(meta) if meta.permissions().readonly() => {
+            eprintln!("Error: {} is not writable", path.display());
+            process::exit(1);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("Error: Cannot access {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+
+    // An empty file is treated as an empty knowledge base
+    let is_empty = fs::read_to_string(&path)
+        .map(|data| data.trim().is_empty())
+        .unwrap_or(false);
+    let mut kb = if is_empty {
+        KnowledgeBase::new()
+    } else {
+        match KnowledgeBase::load(path.clone()) {
+            Ok(kb) => kb,
+            Err(e) => {
+                eprintln!("Error loading {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    };
+
+    kb.add_example(input, ResponseFormat::Text(output), weight);
+
+    match kb.save(Some(path.clone())) {
+        Ok(_) => {
+            println!(
+                "Example added to {} ({} examples). Rebuild to embed it.",
+                path.display(),
+                kb.get_examples().len()
+            );
+        }
+        Err(e) => {
+            eprintln!("Error saving {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+fn handle_knowledge_dump(args: &[String]) {
+    let path = match args {
+        [path] => PathBuf::from(path),
+        _ => {
+            eprintln!("Error: 'knowledge dump' requires exactly one output path");
+            process::exit(1);
+        }
+    };
+
+    match KnowledgeBase::export_embedded(path.clone()) {
+        Ok(_) => println!(
+            "Embedded training data ({} examples) written to {}",
+            KnowledgeBase::from_embedded().get_examples().len(),
+            path.display()
+        ),
+        Err(e) => {
+            eprintln!("Error writing {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_interactive_mode(source: Option<PathBuf>, quiet: bool) {
     println!("=== Interactive Mode ===");
     println!("Select an agent type:");
     println!("1. Exact (SimpleAgent)");
@@ -136,24 +281,32 @@ fn run_interactive_mode() {
     let choice = input.trim();
 
     // Loading knowledge base
-    let kb = KnowledgeBase::from_embedded();
+    let kb = match load_interactive_kb(source.as_deref()) {
+        Ok(kb) => kb,
+        Err(e) => {
+            eprintln!("Error loading knowledge base: {}", e);
+            process::exit(1);
+        }
+    };
     let examples = kb.get_examples();
+    let source = source.as_deref();
 
     match choice {
-        "1" => interactive_loop_simple(examples),
-        "2" => interactive_loop_fuzzy(examples),
-        "3" => interactive_loop_tfidf(examples),
-        "4" => interactive_loop_context(examples),
+        "1" => interactive_loop_simple(examples, source, quiet),
+        "2" => interactive_loop_fuzzy(examples, source, quiet),
+        "3" => interactive_loop_tfidf(examples, source, quiet),
+        "4" => interactive_loop_context(examples, source, quiet),
         _ => println!("Invalid selection. Please restart the program."),
     }
 }
 
-fn interactive_loop_simple(examples: &[TrainingExample]) {
+fn interactive_loop_simple(examples: &[TrainingExample], source: Option<&Path>, quiet: bool) {
     println!("=== Exact Matching Agent ===");
-    println!("Enter questions or 'exit' to quit.");
+    println!("Enter questions or 'exit' to quit, ':reload' rereads the knowledge base.");
 
     let mut agent = MatchAgent::new_exact();
     agent.train(examples);
+    let mut suggester = question_suggester(examples);
 
     loop {
         print!("> ");
@@ -167,17 +320,26 @@ fn interactive_loop_simple(examples: &[TrainingExample]) {
             break;
         }
 
-        let answer = agent.predict(input);
-        println!("Answer: {}", String::from(answer.clone()));
+        if input == ":reload" {
+            if let Some(examples) = reload_examples(source) {
+                agent.train(&examples);
+                suggester = question_suggester(&examples);
+            }
+            continue;
+        }
+
+        print_answer(&agent.predict_with_metadata(input), quiet);
+        suggest_on_low_confidence(&agent, &suggester, input);
     }
 }
 
-fn interactive_loop_fuzzy(examples: &[TrainingExample]) {
+fn interactive_loop_fuzzy(examples: &[TrainingExample], source: Option<&Path>, quiet: bool) {
     println!("=== Fuzzy Matching Agent ===");
-    println!("Enter questions or 'exit' to quit.");
+    println!("Enter questions or 'exit' to quit, ':reload' rereads the knowledge base.");
 
     let mut agent = MatchAgent::new_fuzzy();
     agent.train(examples);
+    let mut suggester = question_suggester(examples);
 
     loop {
         print!("> ");
@@ -191,17 +353,26 @@ fn interactive_loop_fuzzy(examples: &[TrainingExample]) {
             break;
         }
 
-        let answer = agent.predict(input);
-        println!("Answer: {}", String::from(answer));
+        if input == ":reload" {
+            if let Some(examples) = reload_examples(source) {
+                agent.train(&examples);
+                suggester = question_suggester(&examples);
+            }
+            continue;
+        }
+
+        print_answer(&agent.predict_with_metadata(input), quiet);
+        suggest_on_low_confidence(&agent, &suggester, input);
     }
 }
 
-fn interactive_loop_tfidf(examples: &[TrainingExample]) {
+fn interactive_loop_tfidf(examples: &[TrainingExample], source: Option<&Path>, quiet: bool) {
     println!("=== TFIDF Agent (BM25) ===");
-    println!("Enter questions or 'exit' to quit.");
+    println!("Enter questions or 'exit' to quit, ':reload' rereads the knowledge base.");
 
     let mut agent = TfidfAgent::new();
     agent.train(examples);
+    let mut suggester = question_suggester(examples);
 
     loop {
         print!("> ");
@@ -215,19 +386,31 @@ fn interactive_loop_tfidf(examples: &[TrainingExample]) {
             break;
         }
 
-        let answer = agent.predict(input);
-        println!("Answer: {}", String::from(answer));
+        if input == ":reload" {
+            if let Some(examples) = reload_examples(source) {
+                agent.train(&examples);
+                suggester = question_suggester(&examples);
+            }
+            continue;
+        }
+
+        print_answer(&agent.predict_with_metadata(input), quiet);
+        suggest_on_low_confidence(&agent, &suggester, input);
     }
 }
 
-fn interactive_loop_context(examples: &[TrainingExample]) {
+fn interactive_loop_context(examples: &[TrainingExample], source: Option<&Path>, quiet: bool) {
     println!("=== Context Agent ===");
-    println!("Enter questions or 'exit' to quit.");
+    let (format_name, format) = select_context_format();
+    println!("Context format: {}", format_name);
+    println!("Enter questions or 'exit' to quit, ':reload' rereads the knowledge base.");
     println!("The agent uses context from previous questions.");
+    println!("':context [question]' shows the input passed on to the base agent.");
 
     let mut base_agent = TfidfAgent::new();
     base_agent.train(examples);
-    let mut agent = ContextAgent::new(base_agent, 3);
+    let mut agent = ContextAgent::new(base_agent, 3).with_context_format(format);
+    let mut suggester = question_suggester(examples);
 
     loop {
         print!("> ");
@@ -241,14 +424,101 @@ fn interactive_loop_context(examples: &[TrainingExample]) {
             break;
         }
 
-        let answer = agent.predict(input);
-        let answer_str = String::from(answer.clone());
-        println!("Answer: {}", answer_str);
-        agent.add_context(input.to_string(), answer);
+        if input == ":reload" {
+            if let Some(examples) = reload_examples(source) {
+                agent.train(&examples);
+                suggester = question_suggester(&examples);
+            }
+            continue;
+        }
+
+        if let Some(question) = input.strip_prefix(":context") {
+            println!(
+                "Enhanced input: {}",
+                agent.debug_enhanced_input(question.trim())
+            );
+            continue;
+        }
+
+        let result = agent.predict_with_metadata(input);
+        print_answer(&result, quiet);
+        suggest_on_low_confidence(&agent, &suggester, input);
+        agent.add_context(input.to_string(), result.response);
+    }
+}
+
+/// Loads the knowledge base for interactive mode from `source`, or the embedded data
+fn load_interactive_kb(source: Option<&Path>) -> Result<KnowledgeBase, String> {
+    match source {
+        Some(path) => KnowledgeBase::load(path.to_path_buf()),
+        None => Ok(KnowledgeBase::from_embedded()),
+    }
+}
+
+/// Rereads the knowledge base for ':reload'. On errors the current agent is kept.
+fn reload_examples(source: Option<&Path>) -> Option<Vec<TrainingExample>> {
+    match load_interactive_kb(source) {
+        Ok(kb) => {
+            println!("Reloaded {} examples.", kb.get_examples().len());
+            Some(kb.get_examples().to_vec())
+        }
+        Err(e) => {
+            println!("Error reloading knowledge base: {}", e);
+            None
+        }
+    }
+}
+
+/// Asks how previous turns are formatted into the context
+fn select_context_format() -> (&'static str, ContextFormat) {
+    println!("Select a context format:");
+    println!("1. Q/A pairs (default)");
+    println!("2. List");
+    println!("3. Sentence");
+
+    match prompt("> ").as_str() {
+        "2" => ("List", ContextFormat::List),
+        "3" => ("Sentence", ContextFormat::Sentence),
+        "" | "1" => ("QAPairs", ContextFormat::QAPairs),
+        other => {
+            println!("Invalid selection '{}', using Q/A pairs.", other);
+            ("QAPairs", ContextFormat::QAPairs)
+        }
+    }
+}
+
+/// Builds a fuzzy agent that maps near-miss questions to the known question
+fn question_suggester(examples: &[TrainingExample]) -> MatchAgent {
+    let questions: Vec<TrainingExample> = examples
+        .iter()
+        .map(|example| TrainingExample {
+            input: example.input.clone(),
+            output: ResponseFormat::Text(example.input.clone()),
+            weight: example.weight,
+            metadata: None,
+        })
+        .collect();
+
+    let mut suggester = MatchAgent::new_fuzzy();
+    suggester.train(&questions);
+    suggester
+}
+
+/// Suggests the closest known question when the agent is unsure about its answer
+fn suggest_on_low_confidence(agent: &impl Agent, suggester: &MatchAgent, input: &str) {
+    if input.is_empty() || agent.confidence(input) >= SUGGESTION_CONFIDENCE {
+        return;
+    }
+
+    if suggester.confidence(input) > 0.0 {
+        let question = String::from(suggester.predict(input));
+        if !question.eq_ignore_ascii_case(input) {
+            println!("Did you mean: {}?", question.trim_end_matches('?'));
+        }
     }
 }
 
-fn run_knowledge_management() {
+fn run_knowledge_management(quiet: bool) {
     println!("=== Knowledge Base Management ===");
     println!("1. Create new knowledge base");
     println!("2. Load knowledge base");
@@ -261,57 +531,18 @@ fn run_knowledge_management() {
     let choice = input.trim();
 
     match choice {
-        "1" => create_knowledge_base(),
-        "2" => load_knowledge_base(),
-        _ => return,
+        "1" => create_knowledge_base(quiet),
+        "2" => load_knowledge_base(quiet),
+        _ => (),
     }
 }
 
-fn create_knowledge_base() {
+fn create_knowledge_base(quiet: bool) {
     let mut kb = KnowledgeBase::new();
 
     println!("=== Create New Knowledge Base ===");
-    println!("Enter examples. Press Enter without input to finish.");
-
-    loop {
-        println!("\nNew example:");
-        print!("Question: ");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
-
-        if input.is_empty() {
-            break;
-        }
-
-        print!("Answer: ");
-        io::stdout().flush().unwrap();
-
-        let mut output = String::new();
-        io::stdin().read_line(&mut output).unwrap();
-        let output = output.trim();
-
-        print!("Weight (Default 1.0): ");
-        io::stdout().flush().unwrap();
-
-        let mut weight_str = String::new();
-        io::stdin().read_line(&mut weight_str).unwrap();
-        let weight_str = weight_str.trim();
-
-        let weight = if weight_str.is_empty() {
-            1.0
-        } else {
-            weight_str.parse::<f32>().unwrap_or(1.0)
-        };
-
-        kb.add_example(
-            input.to_string(),
-            ResponseFormat::Text(output.to_string()),
-            weight,
-        );
-        println!("Example added!");
+    for example in read_examples() {
+        kb.add_example(example.input, example.output, example.weight);
     }
 
     println!("\nEnter path to save:");
@@ -340,14 +571,110 @@ fn create_knowledge_base() {
             let test = test.trim();
 
             if test.to_lowercase() == "y" {
-                test_knowledge_base(&kb);
+                test_knowledge_base(&kb, quiet);
             }
         }
         Err(e) => println!("Error saving: {}", e),
     }
 }
 
-fn load_knowledge_base() {
+/// Reads a line from stdin after showing `label`, without the trailing newline
+fn prompt(label: &str) -> String {
+    print!("{}", label);
+    io::stdout().flush().unwrap();
+
+    let mut line = String::new();
+    io::stdin().read_line(&mut line).unwrap();
+    line.trim().to_string()
+}
+
+/// Turns an entered answer into a response. Answers that parse as a JSON object
+/// or array can be stored as `ResponseFormat::Json` after confirmation.
+fn read_answer(answer: String) -> ResponseFormat {
+    match serde_json::from_str::<serde_json::Value>(&answer) {
+        Ok(value) if value.is_object() || value.is_array() => {
+            let store_json = prompt("The answer is valid JSON. Store it as JSON? (y/n): ");
+            if store_json.to_lowercase() == "y" {
+                ResponseFormat::Json(value)
+            } else {
+                ResponseFormat::Text(answer)
+            }
+        }
+        _ => ResponseFormat::Text(answer),
+    }
+}
+
+/// Prompts for answer and weight of an example whose question is already known
+fn read_example(input: String) -> TrainingExample {
+    let output = read_answer(prompt("Answer: "));
+    let weight_str = prompt("Weight (Default 1.0): ");
+
+    let weight = if weight_str.is_empty() {
+        1.0
+    } else {
+        weight_str.parse::<f32>().unwrap_or(1.0)
+    };
+
+    TrainingExample {
+        input,
+        output,
+        weight,
+        metadata: None,
+    }
+}
+
+/// Collects examples interactively until an empty question is entered.
+/// Supports 'undo', 'list' and 'edit N' for the examples entered in this session.
+fn read_examples() -> Vec<TrainingExample> {
+    let mut examples: Vec<TrainingExample> = Vec::new();
+
+    println!("Enter examples. Press Enter without input to finish.");
+    println!("Commands: 'undo' removes the last example, 'list' shows all, 'edit N' changes one.");
+
+    loop {
+        println!("\nNew example:");
+        let input = prompt("Question: ");
+
+        if input.is_empty() {
+            break;
+        }
+
+        match input.as_str() {
+            "undo" => match examples.pop() {
+                Some(example) => println!("Removed: {}", example.input),
+                None => println!("Nothing to undo."),
+            },
+            "list" => {
+                if examples.is_empty() {
+                    println!("No examples added yet.");
+                }
+                for (i, example) in examples.iter().enumerate() {
+                    println!(
+                        "{}. {} -> {} (weight {})",
+                        i, example.input, example.output, example.weight
+                    );
+                }
+            }
+            _ if input.starts_with("edit ") => match input[5..].trim().parse::<usize>() {
+                Ok(index) if index < examples.len() => {
+                    println!("Editing example {}: {}", index, examples[index].input);
+                    let question = prompt("Question: ");
+                    examples[index] = read_example(question);
+                    println!("Example updated!");
+                }
+                _ => println!("Invalid example number. Use 'list' to see all examples."),
+            },
+            _ => {
+                examples.push(read_example(input));
+                println!("Example added!");
+            }
+        }
+    }
+
+    examples
+}
+
+fn load_knowledge_base(quiet: bool) {
     println!("Enter path to knowledge base:");
     print!("> ");
     io::stdout().flush().unwrap();
@@ -380,16 +707,16 @@ fn load_knowledge_base() {
             let choice = choice.trim();
 
             match choice {
-                "1" => test_knowledge_base(&kb),
+                "1" => test_knowledge_base(&kb, quiet),
                 "2" => add_examples_to_kb(kb),
-                _ => return,
+                _ => (),
             }
         }
         Err(e) => println!("Error loading: {}", e),
     }
 }
 
-fn test_knowledge_base(kb: &KnowledgeBase) {
+fn test_knowledge_base(kb: &KnowledgeBase, quiet: bool) {
     println!("=== Test Knowledge Base ===");
     println!("Select an agent type for testing:");
     println!("1. Exact (SimpleAgent)");
@@ -408,24 +735,25 @@ fn test_knowledge_base(kb: &KnowledgeBase) {
         "1" => {
             let mut agent = MatchAgent::new_exact();
             agent.train(examples);
-            test_loop(&agent)
+            test_loop(&agent, examples, quiet)
         }
         "2" => {
             let mut agent = MatchAgent::new_fuzzy();
             agent.train(examples);
-            test_loop(&agent)
+            test_loop(&agent, examples, quiet)
         }
         "3" => {
             let mut agent = TfidfAgent::new();
             agent.train(examples);
-            test_loop(&agent)
+            test_loop(&agent, examples, quiet)
         }
         _ => println!("Invalid selection."),
     }
 }
 
-fn test_loop(agent: &impl Agent) {
+fn test_loop(agent: &impl Agent, examples: &[TrainingExample], quiet: bool) {
     println!("Ask questions or enter 'exit' to quit.");
+    let suggester = question_suggester(examples);
 
     loop {
         print!("> ");
@@ -439,54 +767,15 @@ fn test_loop(agent: &impl Agent) {
             break;
         }
 
-        let answer = agent.predict(input);
-        println!("Answer: {}", String::from(answer));
+        print_answer(&agent.predict_with_metadata(input), quiet);
+        suggest_on_low_confidence(agent, &suggester, input);
     }
 }
 
 fn add_examples_to_kb(mut kb: KnowledgeBase) {
     println!("=== Add Examples ===");
-    println!("Enter examples. Press Enter without input to finish.");
-
-    loop {
-        println!("\nNew example:");
-        print!("Question: ");
-        io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim();
-
-        if input.is_empty() {
-            break;
-        }
-
-        print!("Answer: ");
-        io::stdout().flush().unwrap();
-
-        let mut output = String::new();
-        io::stdin().read_line(&mut output).unwrap();
-        let output = output.trim();
-
-        print!("Weight (Default 1.0): ");
-        io::stdout().flush().unwrap();
-
-        let mut weight_str = String::new();
-        io::stdin().read_line(&mut weight_str).unwrap();
-        let weight_str = weight_str.trim();
-
-        let weight = if weight_str.is_empty() {
-            1.0
-        } else {
-            weight_str.parse::<f32>().unwrap_or(1.0)
-        };
-
-        kb.add_example(
-            input.to_string(),
-            ResponseFormat::Text(output.to_string()),
-            weight,
-        );
-        println!("Example added!");
+    for example in read_examples() {
+        kb.add_example(example.input, example.output, example.weight);
     }
 
     println!("\nWould you like to save the changes? (y/n)");
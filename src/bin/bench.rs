@@ -0,0 +1,194 @@
+// src/bin/bench.rs - Measures query latency and throughput of the agents
+use airust::agent::{Agent, TrainableAgent};
+use airust::context_agent::ContextAgent;
+use airust::knowledge::KnowledgeBase;
+use airust::match_agent::MatchAgent;
+use airust::tfidf_agent::TfidfAgent;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, Instant};
+
+fn print_help() {
+    println!("bench - Query benchmark for airust agents");
+    println!();
+    println!("Usage:");
+    println!("  bench <knowledge-file> <query-file> [options]");
+    println!("  bench --help                   - Shows this help");
+    println!();
+    println!("The query file contains one query per line; empty lines are skipped.");
+    println!();
+    println!("Options:");
+    println!("  --agent <type>         - simple, fuzzy, tfidf or context (default: tfidf)");
+    println!("  --iterations <number>  - How often all queries are run (default: 1)");
+}
+
+/// Trains the requested agent on the knowledge base
+fn build_agent(agent_type: &str, kb: &KnowledgeBase) -> Option<Box<dyn Agent>> {
+    let examples = kb.get_examples();
+
+    let agent: Box<dyn Agent> = match agent_type {
+        "simple" => {
+            let mut agent = MatchAgent::new_exact();
+            agent.train(examples);
+            Box::new(agent)
+        }
+        "fuzzy" => {
+            let mut agent = MatchAgent::new_fuzzy();
+            agent.train(examples);
+            Box::new(agent)
+        }
+        "tfidf" => {
+            let mut agent = TfidfAgent::new();
+            agent.train(examples);
+            Box::new(agent)
+        }
+        "context" => {
+            let mut base_agent = TfidfAgent::new();
+            base_agent.train(examples);
+            Box::new(ContextAgent::new(base_agent, 3))
+        }
+        _ => return None,
+    };
+
+    Some(agent)
+}
+
+/// Returns the latency below which `percentile` percent of the samples fall
+fn percentile(sorted: &[Duration], percentile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((percentile / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 || args[1] == "--help" || args[1] == "-h" {
+        print_help();
+        return;
+    }
+
+    let mut agent_type = "tfidf".to_string();
+    let mut iterations: usize = 1;
+    let mut kb_path = String::new();
+    let mut query_path = String::new();
+
+    // Parse arguments
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--agent" => {
+                if i + 1 < args.len() {
+                    agent_type = args[i + 1].clone();
+                    i += 2;
+                } else {
+                    eprintln!("Error: --agent requires a value");
+                    process::exit(1);
+                }
+            }
+            "--iterations" => {
+                if i + 1 < args.len() {
+                    match args[i + 1].parse::<usize>() {
+                        Ok(value) if value > 0 => iterations = value,
+                        _ => {
+                            eprintln!("Error: --iterations requires a positive number");
+                            process::exit(1);
+                        }
+                    }
+                    i += 2;
+                } else {
+                    eprintln!("Error: --iterations requires a value");
+                    process::exit(1);
+                }
+            }
+            _ => {
+                if kb_path.is_empty() {
+                    kb_path = args[i].clone();
+                } else if query_path.is_empty() {
+                    query_path = args[i].clone();
+                } else {
+                    eprintln!("Unknown argument: {}", args[i]);
+                    print_help();
+                    process::exit(1);
+                }
+                i += 1;
+            }
+        }
+    }
+
+    if kb_path.is_empty() || query_path.is_empty() {
+        eprintln!("Error: Knowledge file and query file are required");
+        print_help();
+        process::exit(1);
+    }
+
+    let kb = match KnowledgeBase::load(PathBuf::from(&kb_path)) {
+        Ok(kb) => kb,
+        Err(e) => {
+            eprintln!("Error loading knowledge base: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let queries: Vec<String> = match fs::read_to_string(&query_path) {
+        Ok(content) => content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+        Err(e) => {
+            eprintln!("Error reading query file: {}", e);
+            process::exit(1);
+        }
+    };
+
+    if queries.is_empty() {
+        eprintln!("Error: The query file contains no queries");
+        process::exit(1);
+    }
+
+    let train_start = Instant::now();
+    let agent = match build_agent(&agent_type, &kb) {
+        Some(agent) => agent,
+        None => {
+            eprintln!("Unknown agent type: {}", agent_type);
+            print_help();
+            process::exit(1);
+        }
+    };
+    let train_time = train_start.elapsed();
+
+    // Run all queries, measuring each one
+    let mut latencies: Vec<Duration> = Vec::with_capacity(queries.len() * iterations);
+    let total_start = Instant::now();
+    for _ in 0..iterations {
+        for query in &queries {
+            let start = Instant::now();
+            let answer = agent.predict(query);
+            latencies.push(start.elapsed());
+            std::hint::black_box(answer);
+        }
+    }
+    let total_time = total_start.elapsed();
+
+    latencies.sort();
+    let mean = total_time / latencies.len() as u32;
+    let throughput = latencies.len() as f64 / total_time.as_secs_f64().max(f64::EPSILON);
+
+    println!("Agent: {}", agent_type);
+    println!("Examples: {}", kb.get_examples().len());
+    println!("Queries: {} x {} iterations", queries.len(), iterations);
+    println!("Training time: {:.3} ms", train_time.as_secs_f64() * 1000.0);
+    println!("Total time: {:.3} ms", total_time.as_secs_f64() * 1000.0);
+    println!("Mean latency: {:.3} µs", mean.as_secs_f64() * 1_000_000.0);
+    println!(
+        "p95 latency: {:.3} µs",
+        percentile(&latencies, 95.0).as_secs_f64() * 1_000_000.0
+    );
+    println!("Throughput: {:.1} queries/sec", throughput);
+}
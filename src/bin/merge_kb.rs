@@ -1,12 +1,21 @@
-// src/bin/merge_kb.rs - Tool for merging all JSON files in the knowledge/ directory
+// src/bin/merge_kb.rs - Tool for merging all knowledge files in the knowledge/ directory
 use airust::knowledge::KnowledgeBase;
-use std::fs;
 use std::path::PathBuf;
 use std::process;
 
+/// File formats this build can merge; YAML needs the `yaml` feature
+const FORMATS: &str = if cfg!(feature = "yaml") {
+    "JSON, CSV and YAML"
+} else {
+    "JSON and CSV"
+};
+
 fn main() {
     println!("=== Knowledge Base Merger ===");
-    println!("Searching the knowledge/ directory for JSON files...");
+    println!(
+        "Searching the knowledge/ directory for {} files...",
+        FORMATS
+    );
 
     // Path to the knowledge directory
     let knowledge_dir = PathBuf::from("knowledge");
@@ -17,58 +26,27 @@ fn main() {
         process::exit(1);
     }
 
-    // Find all JSON files in the directory
-    let mut json_files = Vec::new();
-    match fs::read_dir(&knowledge_dir) {
-        Ok(entries) => {
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    let path = entry.path();
-                    if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
-                        json_files.push(path);
-                    }
-                }
-            }
-        }
+    // Load and merge all knowledge files
+    let (mut merged_kb, report) = match KnowledgeBase::load_dir(knowledge_dir.clone()) {
+        Ok(result) => result,
         Err(e) => {
             eprintln!("Error reading directory: {}", e);
             process::exit(1);
         }
-    }
+    };
 
-    // Check if JSON files were found
-    if json_files.is_empty() {
-        eprintln!("No JSON files found in the knowledge/ directory!");
+    // Check if knowledge files were found
+    if report.is_empty() {
+        eprintln!("No {} files found in the knowledge/ directory!", FORMATS);
         process::exit(1);
     }
 
-    println!("{} JSON files found:", json_files.len());
-    for file in &json_files {
-        println!("  - {}", file.file_name().unwrap().to_string_lossy());
-    }
-
-    // Create target Knowledge Base
-    let mut merged_kb = KnowledgeBase::new();
-
-    // Load and merge all JSON files
-    for file in &json_files {
-        match KnowledgeBase::load(file.clone()) {
-            Ok(kb) => {
-                let example_count = kb.get_examples().len();
-                println!(
-                    "File {} loaded: {} examples",
-                    file.file_name().unwrap().to_string_lossy(),
-                    example_count
-                );
-                merged_kb.merge(&kb);
-            }
-            Err(e) => {
-                println!(
-                    "Warning: Could not load file {}: {}",
-                    file.file_name().unwrap().to_string_lossy(),
-                    e
-                );
-            }
+    println!("{} files found:", report.len());
+    for (file, result) in &report {
+        let name = file.file_name().unwrap().to_string_lossy();
+        match result {
+            Ok(example_count) => println!("File {} loaded: {} examples", name, example_count),
+            Err(e) => println!("Warning: Could not load file {}: {}", name, e),
         }
     }
 
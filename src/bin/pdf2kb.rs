@@ -17,9 +17,11 @@ fn print_help() {
     println!("  --min-chunk <number>   - Minimum chunk size (default: 50)");
     println!("  --max-chunk <number>   - Maximum chunk size (default: 1000)");
     println!("  --overlap <number>     - Overlap between chunks (default: 200)");
+    println!("  --overlap-ratio <num>  - Overlap as a fraction of the max. chunk size (0.0-1.0)");
     println!("  --weight <number>      - Weight for training examples (default: 1.0)");
     println!("  --no-metadata          - No metadata in training examples");
     println!("  --no-sentence-split    - Don't split text at sentence boundaries");
+    println!("  --split-by-heading     - Start a new chunk at every heading");
 }
 
 fn main() {
@@ -81,6 +83,20 @@ fn main() {
                     process::exit(1);
                 }
             }
+            "--overlap-ratio" => {
+                if i + 1 < args.len() {
+                    if let Ok(value) = args[i + 1].parse::<f32>() {
+                        config.chunk_overlap_ratio = Some(value);
+                    } else {
+                        eprintln!("Error: --overlap-ratio requires a decimal number");
+                        process::exit(1);
+                    }
+                    i += 2;
+                } else {
+                    eprintln!("Error: --overlap-ratio requires a value");
+                    process::exit(1);
+                }
+            }
             "--weight" => {
                 if i + 1 < args.len() {
                     if let Ok(value) = args[i + 1].parse::<f32>() {
@@ -103,6 +119,10 @@ fn main() {
                 config.split_by_sentence = false;
                 i += 1;
             }
+            "--split-by-heading" => {
+                config.split_by_heading = true;
+                i += 1;
+            }
             _ => {
                 // If it's not an option, it should be a file path
                 if pdf_path.is_empty() {
@@ -155,7 +175,14 @@ fn main() {
     println!("Configuration:");
     println!("  Min. chunk size: {} characters", config.min_chunk_size);
     println!("  Max. chunk size: {} characters", config.max_chunk_size);
-    println!("  Chunk overlap: {} characters", config.chunk_overlap);
+    let chunk_overlap = match config.effective_chunk_overlap() {
+        Ok(overlap) => overlap,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    println!("  Chunk overlap: {} characters", chunk_overlap);
     println!("  Weight: {}", config.default_weight);
     println!(
         "  Metadata: {}",
@@ -170,6 +197,11 @@ fn main() {
         }
     );
 
+    println!(
+        "  Heading split: {}",
+        if config.split_by_heading { "Yes" } else { "No" }
+    );
+
     // Convert PDF to Knowledge Base
     let loader = PdfLoader::with_config(config);
     match loader.pdf_to_knowledge_base(&pdf_path) {
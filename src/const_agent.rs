@@ -0,0 +1,56 @@
+// src/const_agent.rs - Agent with a fixed answer, e.g. as a stub in tests
+use crate::agent::{Agent, ResponseFormat};
+
+/// Answers every query with the same response and confidence, e.g. to test agents that
+/// combine or route between other agents deterministically
+#[derive(Debug, Clone)]
+pub struct ConstAgent {
+    response: ResponseFormat,
+    confidence: f32,
+}
+
+impl ConstAgent {
+    /// Creates an agent that always returns `response` with `confidence`
+    pub fn new(response: impl Into<ResponseFormat>, confidence: f32) -> Self {
+        Self {
+            response: response.into(),
+            confidence,
+        }
+    }
+}
+
+impl Agent for ConstAgent {
+    /// Returns the fixed response regardless of the input
+    fn predict(&self, _input: &str) -> ResponseFormat {
+        self.response.clone()
+    }
+
+    /// Returns the fixed confidence regardless of the input
+    fn confidence(&self, _input: &str) -> f32 {
+        self.confidence
+    }
+
+    /// Returns the variant of the fixed response next to "Text"
+    fn supported_formats(&self) -> Vec<&'static str> {
+        let mut formats = vec!["Text"];
+        if self.response.variant_name() != "Text" {
+            formats.push(self.response.variant_name());
+        }
+        formats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_const_agent() {
+        let agent = ConstAgent::new(ResponseFormat::Markdown("**hi**".to_string()), 0.4);
+        assert_eq!(agent.predict("anything"), agent.predict("else"));
+        assert_eq!(agent.confidence("anything"), 0.4);
+        assert!(!agent.can_answer("anything"));
+        assert_eq!(agent.supported_formats(), vec!["Text", "Markdown"]);
+        assert!(ConstAgent::new("yes", 0.9).can_answer(""));
+    }
+}
@@ -1,31 +1,49 @@
 // src/context_agent.rs - Revised ContextAgent
-use crate::agent::{Agent, ContextualAgent, ResponseFormat, TrainableAgent, TrainingExample};
+use crate::agent::{
+    text_utils, Agent, ContextualAgent, ResponseFormat, TrainableAgent, TrainingExample,
+};
 use std::collections::VecDeque;
 
 /// Context agent wraps another agent and provides context-aware responses
 pub struct ContextAgent<A: Agent> {
     base_agent: A,
-    context_history: VecDeque<(String, ResponseFormat)>, // (Question, Answer)
-    max_context_items: usize,
+    context_history: VecDeque<(String, ResponseFormat, f32)>, // (Question, Answer, Weight)
+    budget: ContextBudget,
     context_format: ContextFormat,
+    context_template: String,
 }
 
+/// Maximum number of times a weighted turn is repeated in the context string
+const MAX_TURN_REPEATS: usize = 5;
+
+/// Template used to merge input and context unless configured otherwise
+pub const DEFAULT_CONTEXT_TEMPLATE: &str = "{input} [Context: {context}]";
+
+/// Limits how much conversation history is kept
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContextBudget {
+    /// Keep at most this many question/answer turns
+    Items(usize),
+    /// Keep turns while the tokens of all questions and answers stay within this total.
+    /// Weighted turns count once for every time they are repeated in the context.
+    Tokens(usize),
+}
+
+/// Custom formatting function for the context history
+pub type ContextFormatter = Box<dyn Fn(&[(String, ResponseFormat)]) -> String>;
+
 /// Configurable context formatting strategies
+#[derive(Default)]
 pub enum ContextFormat {
     /// Format: "Q: question A: answer Q: question A: answer ..."
+    #[default]
     QAPairs,
     /// Format: "[question -> answer, question -> answer, ...]"
     List,
     /// Format: "Previous questions and answers: question - answer; question - answer; ..."
     Sentence,
     /// Custom format with formatting function
-    Custom(Box<dyn Fn(&[(String, ResponseFormat)]) -> String>),
-}
-
-impl Default for ContextFormat {
-    fn default() -> Self {
-        ContextFormat::QAPairs
-    }
+    Custom(ContextFormatter),
 }
 
 impl<A: Agent> ContextAgent<A> {
@@ -34,31 +52,137 @@ impl<A: Agent> ContextAgent<A> {
         Self {
             base_agent,
             context_history: VecDeque::new(),
-            max_context_items,
+            budget: ContextBudget::Items(max_context_items),
             context_format: ContextFormat::default(),
+            context_template: DEFAULT_CONTEXT_TEMPLATE.to_string(),
         }
     }
 
+    /// Sets how the context history is limited
+    pub fn with_budget(mut self, budget: ContextBudget) -> Self {
+        self.budget = budget;
+        self.enforce_budget();
+        self
+    }
+
+    /// Number of times a turn with `weight` appears in the context string (rounded, 1 - 5)
+    fn turn_repeats(weight: f32) -> usize {
+        (weight.round().max(1.0) as usize).min(MAX_TURN_REPEATS)
+    }
+
+    /// Counts the tokens a single question/answer turn adds to the context, repeats included
+    fn turn_tokens((question, answer, weight): &(String, ResponseFormat, f32)) -> usize {
+        let tokens =
+            text_utils::tokenize(question).len() + text_utils::tokenize(&answer.to_string()).len();
+        tokens * Self::turn_repeats(*weight)
+    }
+
+    /// Removes the oldest turn, sparing pinned turns (weight above 1.0) while others remain
+    fn evict_oldest(&mut self) -> Option<(String, ResponseFormat, f32)> {
+        let idx = self
+            .context_history
+            .iter()
+            .position(|&(_, _, weight)| weight <= 1.0)
+            .unwrap_or(0);
+        self.context_history.remove(idx)
+    }
+
+    /// Drops the oldest turns until the history fits the budget
+    fn enforce_budget(&mut self) {
+        match self.budget {
+            ContextBudget::Items(max_items) => {
+                while self.context_history.len() > max_items {
+                    self.evict_oldest();
+                }
+            }
+            ContextBudget::Tokens(max_tokens) => {
+                let mut total: usize = self.context_history.iter().map(Self::turn_tokens).sum();
+                while total > max_tokens {
+                    match self.evict_oldest() {
+                        Some(turn) => total -= Self::turn_tokens(&turn),
+                        None => break,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Expands the history so that each turn appears as often as its weight (rounded, 1 - 5)
+    fn weighted_history(&self) -> Vec<(String, ResponseFormat)> {
+        self.context_history
+            .iter()
+            .flat_map(|(q, a, weight)| {
+                std::iter::repeat_n((q.clone(), a.clone()), Self::turn_repeats(*weight))
+            })
+            .collect()
+    }
+
     /// Sets the context format for generating context strings
     pub fn with_context_format(mut self, format: ContextFormat) -> Self {
         self.context_format = format;
         self
     }
 
-    /// Creates a context string from the conversation history
+    /// Sets how input and context are merged, using the placeholders `{input}` and
+    /// `{context}`. Without history the input is passed on unchanged.
+    pub fn with_context_template(mut self, template: String) -> Self {
+        self.context_template = template;
+        self
+    }
+
+    /// Returns exactly the input that `predict` would pass to the base agent for `input`,
+    /// i.e. the query merged with the formatted history. Meant for debugging.
+    pub fn debug_enhanced_input(&self, input: &str) -> String {
+        self.enhanced_input(input)
+    }
+
+    /// Combines the input with the context string as passed to the base agent
+    fn enhanced_input(&self, input: &str) -> String {
+        // Adds context to input; list and sentence formats are never empty, so check the history
+        if self.context_history.is_empty() {
+            return input.to_string();
+        }
+        let context_str = self.get_context_string();
+        if context_str.is_empty() {
+            return input.to_string();
+        }
+
+        // Substitutes in a single pass, so placeholders inside input or context stay literal
+        let mut enhanced = String::new();
+        let mut rest = self.context_template.as_str();
+        while let Some(start) = rest.find('{') {
+            enhanced.push_str(&rest[..start]);
+            let tail = &rest[start..];
+            if let Some(after) = tail.strip_prefix("{input}") {
+                enhanced.push_str(input);
+                rest = after;
+            } else if let Some(after) = tail.strip_prefix("{context}") {
+                enhanced.push_str(&context_str);
+                rest = after;
+            } else {
+                enhanced.push('{');
+                rest = &tail[1..];
+            }
+        }
+        enhanced.push_str(rest);
+        enhanced
+    }
+
+    /// Creates a context string from the conversation history.
+    /// Turns with a higher weight are repeated to give them more influence.
     fn get_context_string(&self) -> String {
+        let history = self.weighted_history();
         match &self.context_format {
             ContextFormat::QAPairs => {
                 let mut context = String::new();
-                for (q, a) in &self.context_history {
+                for (q, a) in &history {
                     let answer_text: String = a.clone().into();
                     context.push_str(&format!("Q: {} A: {} ", q, answer_text));
                 }
                 context
             }
             ContextFormat::List => {
-                let items: Vec<String> = self
-                    .context_history
+                let items: Vec<String> = history
                     .iter()
                     .map(|(q, a)| {
                         let answer_text: String = a.clone().into();
@@ -68,8 +192,7 @@ impl<A: Agent> ContextAgent<A> {
                 format!("[{}]", items.join(", "))
             }
             ContextFormat::Sentence => {
-                let items: Vec<String> = self
-                    .context_history
+                let items: Vec<String> = history
                     .iter()
                     .map(|(q, a)| {
                         let answer_text: String = a.clone().into();
@@ -78,29 +201,42 @@ impl<A: Agent> ContextAgent<A> {
                     .collect();
                 format!("Previous questions and answers: {}", items.join("; "))
             }
-            ContextFormat::Custom(formatter) => formatter(
-                &self
-                    .context_history
-                    .iter()
-                    .map(|(q, a)| (q.clone(), a.clone()))
-                    .collect::<Vec<_>>(),
-            ),
+            ContextFormat::Custom(formatter) => formatter(&history),
         }
     }
 }
 
 impl<A: Agent> Agent for ContextAgent<A> {
-    /// Generates a response with context added to the input
+    /// Generates a response with context added to the input. Empty queries are passed
+    /// on without context, so the base agent reports them as such.
     fn predict(&self, input: &str) -> ResponseFormat {
-        // Adds context to input
-        let context_str = self.get_context_string();
-        let enhanced_input = if context_str.is_empty() {
-            input.to_string()
-        } else {
-            format!("{} [Context: {}]", input, context_str)
-        };
+        if input.trim().is_empty() {
+            return self.base_agent.predict(input);
+        }
+        self.base_agent.predict(&self.enhanced_input(input))
+    }
 
-        self.base_agent.predict(&enhanced_input)
+    /// Delegates the confidence to the base agent using the same enhanced input
+    fn confidence(&self, input: &str) -> f32 {
+        if input.trim().is_empty() {
+            return self.base_agent.confidence(input);
+        }
+        self.base_agent.confidence(&self.enhanced_input(input))
+    }
+
+    /// Uses the confidence threshold of the base agent
+    fn confidence_threshold(&self) -> f32 {
+        self.base_agent.confidence_threshold()
+    }
+
+    /// Returns the inputs known to the base agent
+    fn known_inputs(&self) -> Vec<&str> {
+        self.base_agent.known_inputs()
+    }
+
+    /// Returns the formats of the base agent
+    fn supported_formats(&self) -> Vec<&'static str> {
+        self.base_agent.supported_formats()
     }
 }
 
@@ -114,12 +250,15 @@ impl<A: TrainableAgent> TrainableAgent for ContextAgent<A> {
 impl<A: Agent> ContextualAgent for ContextAgent<A> {
     /// Adds a new context item to the conversation history
     fn add_context(&mut self, question: String, answer: ResponseFormat) {
-        self.context_history.push_back((question, answer));
+        self.add_context_weighted(question, answer, 1.0);
+    }
+
+    /// Adds a weighted context item; turns weighted above 1.0 are pinned and repeated
+    fn add_context_weighted(&mut self, question: String, answer: ResponseFormat, weight: f32) {
+        self.context_history.push_back((question, answer, weight));
 
         // Keeps size under maximum
-        while self.context_history.len() > self.max_context_items {
-            self.context_history.pop_front();
-        }
+        self.enforce_budget();
     }
 
     /// Clears the entire context history
@@ -127,3 +266,142 @@ impl<A: Agent> ContextualAgent for ContextAgent<A> {
         self.context_history.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::match_agent::MatchAgent;
+
+    fn long_answer() -> ResponseFormat {
+        ResponseFormat::Text("one two three four five six seven eight nine ten".to_string())
+    }
+
+    #[test]
+    fn test_empty_query_ignores_context() {
+        let mut base = MatchAgent::new_fuzzy();
+        base.train(&[TrainingExample {
+            input: "hello".to_string(),
+            output: ResponseFormat::Text("world".to_string()),
+            weight: 1.0,
+            metadata: None,
+        }]);
+        let mut agent = ContextAgent::new(base, 3);
+        agent.add_text_context("hello".to_string(), "world".to_string());
+
+        assert_eq!(agent.predict_text("  "), crate::agent::EMPTY_QUERY_RESPONSE);
+        assert_eq!(agent.confidence("  "), 0.0);
+    }
+
+    #[test]
+    fn test_item_budget() {
+        let mut agent = ContextAgent::new(MatchAgent::new_exact(), 2);
+        for i in 0..4 {
+            agent.add_text_context(format!("question {}", i), "answer".to_string());
+        }
+
+        assert_eq!(agent.context_history.len(), 2);
+        assert_eq!(agent.context_history[0].0, "question 2");
+    }
+
+    #[test]
+    fn test_token_budget_keeps_fewer_long_turns() {
+        // Each turn has 2 question tokens and 10 answer tokens
+        let mut agent =
+            ContextAgent::new(MatchAgent::new_exact(), 5).with_budget(ContextBudget::Tokens(30));
+        for i in 0..5 {
+            agent.add_context(format!("question {}", i), long_answer());
+        }
+
+        // An item budget of 5 would keep all turns, the token budget only fits two
+        assert_eq!(agent.context_history.len(), 2);
+        assert_eq!(agent.context_history[0].0, "question 3");
+        assert_eq!(agent.context_history[1].0, "question 4");
+    }
+
+    #[test]
+    fn test_token_budget_counts_weighted_repeats() {
+        // The pinned turn has 3 tokens and is repeated 5 times, the others have 2 tokens
+        let mut agent =
+            ContextAgent::new(MatchAgent::new_exact(), 10).with_budget(ContextBudget::Tokens(19));
+        agent.add_context_weighted(
+            "account id".to_string(),
+            ResponseFormat::Text("secret".to_string()),
+            5.0,
+        );
+        for i in 0..4 {
+            agent.add_text_context(format!("question {}", i), "answer".to_string());
+        }
+
+        assert_eq!(agent.context_history.len(), 3);
+        assert_eq!(agent.context_history[0].0, "account id");
+        assert_eq!(agent.context_history[1].0, "question 2");
+
+        let context_tokens: usize = agent
+            .weighted_history()
+            .iter()
+            .map(|(q, a)| {
+                text_utils::tokenize(q).len() + text_utils::tokenize(&a.to_string()).len()
+            })
+            .sum();
+        assert_eq!(context_tokens, 19);
+    }
+
+    #[test]
+    fn test_debug_enhanced_input_shows_history_and_query() {
+        let mut agent =
+            ContextAgent::new(MatchAgent::new_exact(), 3).with_context_format(ContextFormat::List);
+        assert_eq!(agent.debug_enhanced_input("hello"), "hello");
+
+        agent.add_text_context("What is airust?".to_string(), "A library".to_string());
+        agent.add_text_context("Who wrote it?".to_string(), "Its authors".to_string());
+
+        let debug = agent.debug_enhanced_input("Is it fast?");
+        assert_eq!(
+            debug,
+            "Is it fast? [Context: [What is airust? -> A library, Who wrote it? -> Its authors]]"
+        );
+        assert_eq!(debug, agent.enhanced_input("Is it fast?"));
+    }
+
+    #[test]
+    fn test_context_template() {
+        let mut agent = ContextAgent::new(MatchAgent::new_exact(), 3);
+        assert_eq!(agent.enhanced_input("next?"), "next?");
+
+        agent.add_text_context("first".to_string(), "one".to_string());
+        assert_eq!(
+            agent.enhanced_input("next?"),
+            "next? [Context: Q: first A: one ]"
+        );
+
+        let agent =
+            agent.with_context_template("Context: {context}| Question: {input}".to_string());
+        assert_eq!(
+            agent.enhanced_input("what about [x] and {context}?"),
+            "Context: Q: first A: one | Question: what about [x] and {context}?"
+        );
+    }
+
+    #[test]
+    fn test_weighted_turn_is_pinned_and_emphasized() {
+        let mut agent =
+            ContextAgent::new(MatchAgent::new_exact(), 3).with_context_format(ContextFormat::List);
+        agent.add_context_weighted(
+            "account id".to_string(),
+            ResponseFormat::Text("42".to_string()),
+            3.0,
+        );
+        for i in 0..4 {
+            agent.add_text_context(format!("question {}", i), "answer".to_string());
+        }
+
+        // The pinned turn survives newer turns and is repeated according to its weight
+        assert_eq!(agent.context_history.len(), 3);
+        assert_eq!(agent.context_history[0].0, "account id");
+        assert_eq!(
+            agent.get_context_string(),
+            "[account id -> 42, account id -> 42, account id -> 42, \
+             question 2 -> answer, question 3 -> answer]"
+        );
+    }
+}
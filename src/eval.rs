@@ -0,0 +1,241 @@
+// src/eval.rs - Helpers for analyzing retrieval quality
+use crate::agent::{Agent, TrainableAgent, TrainingExample};
+use crate::knowledge::KnowledgeBase;
+use crate::tfidf_agent::TfidfAgent;
+use std::fs;
+use std::path::Path;
+
+/// Best BM25 parameters found by `grid_search_bm25`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BestParams {
+    /// Term frequency scaling
+    pub k1: f32,
+    /// Length normalization
+    pub b: f32,
+    /// Share of held-out examples answered with their own answer (0.0 - 1.0)
+    pub accuracy: f32,
+}
+
+/// Runs the input of every example in `kb` through `agent` and returns the indices of
+/// examples whose own input does not retrieve their own answer, e.g. because another
+/// example with a similar input wins. `agent` is expected to be trained on `kb`.
+pub fn self_consistency(agent: &impl Agent, kb: &KnowledgeBase) -> Vec<usize> {
+    kb.get_examples()
+        .iter()
+        .enumerate()
+        .filter(|(_, example)| agent.predict(&example.input) != example.output)
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Splits examples into `folds` (train, test) pairs for cross-validation. Example `i`
+/// is tested in fold `i % folds`, so the split is deterministic; folds beyond the
+/// number of examples are left out instead of producing empty test sets.
+pub fn k_fold_splits(
+    examples: &[TrainingExample],
+    folds: usize,
+) -> Vec<(Vec<TrainingExample>, Vec<TrainingExample>)> {
+    let folds = folds.min(examples.len());
+
+    (0..folds)
+        .map(|fold| {
+            let (test, train): (Vec<_>, Vec<_>) = examples
+                .iter()
+                .enumerate()
+                .partition(|(idx, _)| idx % folds == fold);
+            (
+                train.into_iter().map(|(_, ex)| ex.clone()).collect(),
+                test.into_iter().map(|(_, ex)| ex.clone()).collect(),
+            )
+        })
+        .collect()
+}
+
+/// Picks the BM25 `(k1, b)` pair from `param_grid` with the best k-fold cross-validated
+/// accuracy on `kb`. For every fold and pair a `TfidfAgent` is trained on the remaining
+/// examples; a held-out example counts as correct if the agent answers it with exactly
+/// its stored answer, so the corpus needs several phrasings per answer to be informative.
+/// Ties go to the pair listed first.
+pub fn grid_search_bm25(
+    kb: &KnowledgeBase,
+    param_grid: &[(f32, f32)],
+    folds: usize,
+) -> Result<BestParams, String> {
+    if param_grid.is_empty() {
+        return Err("The parameter grid is empty".to_string());
+    }
+    if folds < 2 {
+        return Err("Cross-validation needs at least 2 folds".to_string());
+    }
+    let splits = k_fold_splits(kb.get_examples(), folds);
+    if splits.len() < 2 {
+        return Err("Cross-validation needs at least 2 examples".to_string());
+    }
+
+    let mut best: Option<BestParams> = None;
+    for &(k1, b) in param_grid {
+        let mut correct = 0;
+        let mut total = 0;
+        for (train, test) in &splits {
+            let mut agent = TfidfAgent::new().with_bm25_params(k1, b);
+            agent.train(train);
+            for example in test {
+                if agent.predict_ref(&example.input) == Some(&example.output) {
+                    correct += 1;
+                }
+                total += 1;
+            }
+        }
+
+        let accuracy = correct as f32 / total as f32;
+        if best.is_none_or(|best| accuracy > best.accuracy) {
+            best = Some(BestParams { k1, b, accuracy });
+        }
+    }
+
+    // The grid is not empty, so a best pair exists
+    best.ok_or_else(|| "No parameters evaluated".to_string())
+}
+
+/// Quotes a CSV field if it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes the top `n` matches of every query to a CSV file with the columns
+/// `query,rank,example_index,score,answer` (rank starts at 1), e.g. for spreadsheet
+/// analysis. Queries with fewer than `n` matches produce fewer rows.
+/// Returns the number of written rows without the header.
+pub fn rankings_to_csv<P: AsRef<Path>>(
+    agent: &TfidfAgent,
+    queries: &[&str],
+    n: usize,
+    path: P,
+) -> Result<usize, String> {
+    let mut csv = String::from("query,rank,example_index,score,answer\n");
+    let mut rows = 0;
+
+    for query in queries {
+        for (rank, result) in agent.predict_top_n(query, n).into_iter().enumerate() {
+            let example_index = result
+                .metadata
+                .as_ref()
+                .and_then(|m| m["example_index"].as_u64())
+                .map_or(String::new(), |idx| idx.to_string());
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                csv_field(query),
+                rank + 1,
+                example_index,
+                result.confidence,
+                csv_field(&String::from(result.response))
+            ));
+            rows += 1;
+        }
+    }
+
+    fs::write(path, csv).map_err(|e| format!("Failed to write to file: {}", e))?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::ResponseFormat;
+
+    #[test]
+    fn test_self_consistency_flags_ambiguous_pair() {
+        let mut kb = KnowledgeBase::new();
+        kb.add_example(
+            "how do I reset my password".to_string(),
+            "Use the settings page",
+            1.0,
+        );
+        kb.add_example("what is cargo".to_string(), "The package manager", 1.0);
+        kb.add_example(
+            "how do I reset my password".to_string(),
+            "Contact support",
+            1.0,
+        );
+
+        let mut agent = TfidfAgent::new();
+        agent.train(kb.get_examples());
+        assert_eq!(self_consistency(&agent, &kb), vec![2]);
+
+        // Without the conflicting answer every example retrieves itself
+        let mut consistent = KnowledgeBase::new();
+        for example in &kb.get_examples()[..2] {
+            consistent.add_example(example.input.clone(), example.output.clone(), 1.0);
+        }
+        let mut agent = TfidfAgent::new();
+        agent.train(consistent.get_examples());
+        assert!(self_consistency(&agent, &consistent).is_empty());
+    }
+
+    #[test]
+    fn test_grid_search_bm25() {
+        let mut kb = KnowledgeBase::new();
+        for (input, output) in [
+            ("how do I install rust", "Use rustup"),
+            ("rust install instructions", "Use rustup"),
+            ("installing the rust toolchain", "Use rustup"),
+            ("what is cargo", "The package manager"),
+            ("cargo package manager usage", "The package manager"),
+            ("explain cargo", "The package manager"),
+        ] {
+            kb.add_example(input.to_string(), output, 1.0);
+        }
+
+        let grid = [(1.2, 0.75), (2.0, 0.0), (0.5, 1.0)];
+        let best = grid_search_bm25(&kb, &grid, 3).unwrap();
+        assert!(grid.contains(&(best.k1, best.b)));
+        assert!((0.0..=1.0).contains(&best.accuracy));
+
+        // More folds than examples and leave-one-out still work
+        assert!(grid_search_bm25(&kb, &grid, 50).is_ok());
+        assert_eq!(k_fold_splits(kb.get_examples(), 50).len(), 6);
+
+        assert!(grid_search_bm25(&kb, &[], 3).is_err());
+        assert!(grid_search_bm25(&kb, &grid, 1).is_err());
+        assert!(grid_search_bm25(&KnowledgeBase::new(), &grid, 3).is_err());
+    }
+
+    #[test]
+    fn test_rankings_to_csv() {
+        let data: Vec<TrainingExample> = [
+            ("rust ownership rules", "Ownership, explained"),
+            ("rust borrow checker", "He said \"borrow\""),
+            ("rust macros", "Macros"),
+        ]
+        .iter()
+        .map(|(input, output)| TrainingExample {
+            input: input.to_string(),
+            output: ResponseFormat::Text(output.to_string()),
+            weight: 1.0,
+            metadata: None,
+        })
+        .collect();
+        let mut agent = TfidfAgent::new();
+        agent.train(&data);
+
+        let path = std::env::temp_dir().join(format!("airust_rankings_{}.csv", std::process::id()));
+        let rows = rankings_to_csv(&agent, &["rust ownership", "borrow, rust"], 2, &path).unwrap();
+        assert_eq!(rows, 4);
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "query,rank,example_index,score,answer");
+        assert!(lines[1].starts_with("rust ownership,1,0,"));
+        assert!(lines[1].ends_with(",\"Ownership, explained\""));
+        assert!(lines[3].starts_with("\"borrow, rust\",1,1,"));
+        assert!(lines[3].ends_with(",\"He said \"\"borrow\"\"\""));
+        assert!(lines[4].starts_with("\"borrow, rust\",2,"));
+
+        fs::remove_file(path).ok();
+    }
+}
@@ -1,20 +1,271 @@
 // src/knowledge.rs - Unified Knowledge Base
-use crate::agent::{LegacyTrainingExample, ResponseFormat, TrainingExample};
+use crate::agent::{
+    default_weight, text_utils, LegacyTrainingExample, ResponseFormat, TrainingExample,
+};
+use crate::tfidf_agent::{TfidfAgent, WeightMode};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-/// Supports both legacy and modern training data formats for backward compatibility
+/// Supports both legacy and modern training data formats for backward compatibility.
+///
+/// Variants are tried in declaration order: `Modern` first, so that examples with a
+/// tagged output (`{"Text": "..."}`) keep their metadata, then `Legacy` for files with
+/// plain-string outputs, then the versioned layout written by `save`.
 #[derive(Serialize, Deserialize)]
 #[serde(untagged)]
 enum TrainingData {
-    Legacy(Vec<LegacyTrainingExample>),
     Modern(Vec<TrainingExample>),
+    Legacy(Vec<LegacyTrainingExample>),
+    Versioned {
+        version: String,
+        examples: Vec<TrainingExample>,
+    },
+}
+
+/// Versioned file layout written by `save`
+#[derive(Serialize)]
+struct VersionedTrainingData<'a> {
+    version: &'a str,
+    examples: &'a [TrainingExample],
+}
+
+/// Compares the crate version stored in a serialized artifact with the running one.
+/// A mismatch is an error in strict mode and a warning otherwise.
+pub fn check_version(stored: &str, strict: bool) -> Result<(), String> {
+    if stored == crate::VERSION {
+        return Ok(());
+    }
+
+    let message = format!(
+        "Version mismatch: artifact was created by version {}, running version {}",
+        stored,
+        crate::VERSION
+    );
+
+    if strict {
+        Err(message)
+    } else {
+        eprintln!("Warning: {}", message);
+        Ok(())
+    }
+}
+
+/// Parses training data in any supported format, returning the stored version if present
+fn parse_training_data(raw: &str) -> Result<(Vec<TrainingExample>, Option<String>), String> {
+    match serde_json::from_str::<TrainingData>(raw) {
+        Ok(TrainingData::Modern(examples)) => Ok((examples, None)),
+        Ok(TrainingData::Legacy(legacy)) => {
+            // Converts legacy data into the modern format
+            Ok((legacy.into_iter().map(|ex| ex.into()).collect(), None))
+        }
+        Ok(TrainingData::Versioned { version, examples }) => Ok((examples, Some(version))),
+        Err(e) => Err(format!("Deserialization error: {}", e)),
+    }
+}
+
+/// Removes a leading UTF-8 byte order mark, as written by some Windows editors
+fn strip_bom(raw: &str) -> &str {
+    raw.strip_prefix('\u{feff}').unwrap_or(raw)
+}
+
+/// Removes commas that directly precede a closing `]` or `}` (ignoring whitespace),
+/// leaving commas inside strings untouched
+fn strip_trailing_commas(raw: &str) -> String {
+    let chars: Vec<char> = raw.chars().collect();
+    let mut result = String::with_capacity(raw.len());
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+            if matches!(next, Some(']') | Some('}')) {
+                continue;
+            }
+        }
+        result.push(c);
+    }
+
+    result
 }
 
+/// Splits CSV text into records of fields. Fields may be quoted with `"` to contain
+/// separators or line breaks, and `""` inside a quoted field stands for one quote.
+/// Empty lines are skipped.
+fn parse_csv_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                if !(record.len() == 1 && record[0].is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err("CSV error: unterminated quoted field".to_string());
+    }
+    record.push(field);
+    if !(record.len() == 1 && record[0].is_empty()) {
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Per-file outcome of `KnowledgeBase::load_dir`: the number of examples or the error
+pub type DirReport = Vec<(PathBuf, Result<usize, String>)>;
+
+/// Turns text into the terms used by `KnowledgeBase::search`
+pub type Tokenizer<'a> = dyn Fn(&str) -> Vec<String> + 'a;
+
+/// A shape problem found by `KnowledgeBase::validate_file`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    /// Position of the offending example, `None` for problems with the file as a whole
+    pub index: Option<usize>,
+    /// Name of the offending field, empty for the example or file itself
+    pub field: String,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl SchemaError {
+    fn new(index: Option<usize>, field: &str, message: impl Into<String>) -> Self {
+        Self {
+            index,
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.index, self.field.is_empty()) {
+            (Some(index), false) => {
+                write!(f, "example {}: {}: {}", index, self.field, self.message)
+            }
+            (Some(index), true) => write!(f, "example {}: {}", index, self.message),
+            (None, false) => write!(f, "{}: {}", self.field, self.message),
+            (None, true) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Checks the shape of a single example, in either the legacy or the modern format
+fn validate_example(index: usize, value: &serde_json::Value, errors: &mut Vec<SchemaError>) {
+    let object = match value.as_object() {
+        Some(object) => object,
+        None => {
+            errors.push(SchemaError::new(
+                Some(index),
+                "",
+                "example must be an object",
+            ));
+            return;
+        }
+    };
+
+    match object.get("input") {
+        Some(serde_json::Value::String(_)) => {}
+        Some(_) => errors.push(SchemaError::new(Some(index), "input", "must be a string")),
+        None => errors.push(SchemaError::new(Some(index), "input", "is missing")),
+    }
+
+    match object.get("output") {
+        // Legacy format
+        Some(serde_json::Value::String(_)) => {}
+        Some(serde_json::Value::Object(format)) if format.len() == 1 => {
+            let (kind, content) = format.iter().next().unwrap();
+            match kind.as_str() {
+                "Text" | "Markdown" if !content.is_string() => errors.push(SchemaError::new(
+                    Some(index),
+                    "output",
+                    format!("{} content must be a string", kind),
+                )),
+                "Text" | "Markdown" | "Json" => {}
+                _ => errors.push(SchemaError::new(
+                    Some(index),
+                    "output",
+                    format!("unknown format '{}', expected Text, Markdown or Json", kind),
+                )),
+            }
+        }
+        Some(_) => errors.push(SchemaError::new(
+            Some(index),
+            "output",
+            "must be a string or an object with one of Text, Markdown or Json",
+        )),
+        None => errors.push(SchemaError::new(Some(index), "output", "is missing")),
+    }
+
+    if let Some(weight) = object.get("weight") {
+        if !weight.is_number() {
+            errors.push(SchemaError::new(Some(index), "weight", "must be a number"));
+        }
+    }
+}
+
+/// Transformations used by `KnowledgeBase::augment_inputs` to derive input variants
+#[derive(Debug, Clone)]
+pub struct AugmentOptions {
+    /// Adds the lowercased input
+    pub lowercase: bool,
+    /// Adds the input without punctuation
+    pub strip_punctuation: bool,
+    /// Adds the input without stopwords (lowercased, punctuation removed)
+    pub remove_stopwords: bool,
+    /// Language of the stopwords to remove
+    pub stopword_lang: String,
+}
+
+impl Default for AugmentOptions {
+    fn default() -> Self {
+        Self {
+            lowercase: true,
+            strip_punctuation: true,
+            remove_stopwords: true,
+            stopword_lang: "en".to_string(),
+        }
+    }
+}
+
+/// Metadata key marking examples added by `KnowledgeBase::augment_inputs`
+pub const AUGMENTED_METADATA_KEY: &str = "augmented";
+
 /// Represents a flexible knowledge base for storing and managing training examples
 #[derive(Clone)]
 pub struct KnowledgeBase {
@@ -26,12 +277,8 @@ pub struct KnowledgeBase {
 pub static EMBEDDED_DATA: Lazy<Arc<Vec<TrainingExample>>> = Lazy::new(|| {
     let raw = include_str!(concat!(env!("OUT_DIR"), "/train.json"));
 
-    match serde_json::from_str::<TrainingData>(raw) {
-        Ok(TrainingData::Modern(examples)) => Arc::new(examples),
-        Ok(TrainingData::Legacy(legacy)) => {
-            // Converts legacy data into the modern format
-            Arc::new(legacy.into_iter().map(|ex| ex.into()).collect())
-        }
+    match parse_training_data(raw) {
+        Ok((examples, _)) => Arc::new(examples),
         Err(e) => {
             eprintln!("Error loading embedded training data: {}", e);
             Arc::new(Vec::new())
@@ -48,6 +295,14 @@ impl KnowledgeBase {
         }
     }
 
+    /// Creates a knowledge base from existing training examples
+    pub fn from_examples(examples: Vec<TrainingExample>) -> Self {
+        Self {
+            examples,
+            file_path: None,
+        }
+    }
+
     /// Creates a knowledge base from embedded data
     pub fn from_embedded() -> Self {
         Self {
@@ -56,34 +311,258 @@ impl KnowledgeBase {
         }
     }
 
-    /// Loads a knowledge base from a JSON file
+    /// Loads a knowledge base from a JSON file, warning if it was saved by another version
     pub fn load(path: PathBuf) -> Result<Self, String> {
+        Self::load_checked(path, false)
+    }
+
+    /// Like `load`, but also accepts trailing commas before `]` and `}` as left behind
+    /// by hand edits
+    pub fn load_lenient(path: PathBuf) -> Result<Self, String> {
         let data = fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))?;
 
-        match serde_json::from_str::<TrainingData>(&data) {
-            Ok(TrainingData::Modern(examples)) => Ok(Self {
-                examples,
-                file_path: Some(path),
-            }),
-            Ok(TrainingData::Legacy(legacy)) => {
-                // Converts legacy data
-                Ok(Self {
-                    examples: legacy.into_iter().map(|ex| ex.into()).collect(),
-                    file_path: Some(path),
-                })
+        let mut kb = Self::from_json_str(&strip_trailing_commas(strip_bom(&data)))?;
+        kb.file_path = Some(path);
+        Ok(kb)
+    }
+
+    /// Checks the shape of a knowledge file without loading it and reports every problem
+    /// found, with the index of the offending example, instead of stopping at the first one
+    pub fn validate_file(path: PathBuf) -> Result<(), Vec<SchemaError>> {
+        let raw = fs::read_to_string(&path).map_err(|e| {
+            vec![SchemaError::new(
+                None,
+                "",
+                format!("Error reading file: {}", e),
+            )]
+        })?;
+        let value: serde_json::Value = serde_json::from_str(strip_bom(&raw))
+            .map_err(|e| vec![SchemaError::new(None, "", format!("Invalid JSON: {}", e))])?;
+
+        let examples = match &value {
+            serde_json::Value::Array(examples) => examples,
+            serde_json::Value::Object(object) => {
+                let mut errors = Vec::new();
+                if !object.get("version").is_some_and(|v| v.is_string()) {
+                    errors.push(SchemaError::new(None, "version", "must be a string"));
+                }
+                match object.get("examples") {
+                    Some(serde_json::Value::Array(examples)) if errors.is_empty() => examples,
+                    Some(serde_json::Value::Array(_)) => return Err(errors),
+                    _ => {
+                        errors.push(SchemaError::new(None, "examples", "must be an array"));
+                        return Err(errors);
+                    }
+                }
+            }
+            _ => {
+                return Err(vec![SchemaError::new(
+                    None,
+                    "",
+                    "expected an array of examples or a versioned object",
+                )])
             }
-            Err(e) => Err(format!("Deserialization error: {}", e)),
+        };
+
+        let mut errors = Vec::new();
+        for (index, example) in examples.iter().enumerate() {
+            validate_example(index, example, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Loads a knowledge base and records the file name as `source_file` in the metadata
+    /// of every example, so the provenance survives merging. Examples whose metadata is
+    /// not a JSON object are left unchanged.
+    pub fn load_with_source(path: PathBuf) -> Result<Self, String> {
+        let mut kb = Self::load(path.clone())?;
+        kb.tag_source(&path);
+        Ok(kb)
+    }
+
+    /// Records the file name of `path` as `source_file` in the metadata of every example
+    fn tag_source(&mut self, path: &Path) {
+        let source = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string_lossy().to_string());
+
+        for example in &mut self.examples {
+            let metadata = example
+                .metadata
+                .get_or_insert_with(|| serde_json::json!({}));
+            if let Some(object) = metadata.as_object_mut() {
+                object.insert("source_file".to_string(), serde_json::json!(source));
+            }
+        }
+    }
+
+    /// Parses a knowledge base from CSV with a header row naming the columns `input`,
+    /// `output` and optionally `weight` (in any order, case-insensitive). Outputs become
+    /// text answers; an empty weight takes the default.
+    pub fn from_csv_str(csv: &str) -> Result<Self, String> {
+        let mut records = parse_csv_records(csv)?.into_iter();
+        let header: Vec<String> = match records.next() {
+            Some(header) => header.iter().map(|h| h.trim().to_lowercase()).collect(),
+            None => return Ok(Self::new()),
+        };
+        let column = |name: &str| header.iter().position(|h| h == name);
+        let (input_col, output_col) = match (column("input"), column("output")) {
+            (Some(input), Some(output)) => (input, output),
+            _ => return Err("CSV error: header must contain 'input' and 'output'".to_string()),
+        };
+        let weight_col = column("weight");
+
+        let mut examples = Vec::new();
+        for (row, record) in records.enumerate() {
+            // Rows are counted from 1, the header being row 0
+            let field = |col: usize| {
+                record
+                    .get(col)
+                    .ok_or_else(|| format!("CSV error: row {} has too few fields", row + 1))
+            };
+            let weight = match weight_col.map(field).transpose()? {
+                Some(weight) if !weight.trim().is_empty() => {
+                    weight.trim().parse::<f32>().map_err(|_| {
+                        format!(
+                            "CSV error: row {} has an invalid weight '{}'",
+                            row + 1,
+                            weight
+                        )
+                    })?
+                }
+                _ => default_weight(),
+            };
+
+            examples.push(TrainingExample {
+                input: field(input_col)?.clone(),
+                output: ResponseFormat::Text(field(output_col)?.clone()),
+                weight,
+                metadata: None,
+            });
+        }
+
+        Ok(Self::from_examples(examples))
+    }
+
+    /// Parses a knowledge base from YAML in any layout supported for JSON
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(yaml: &str) -> Result<Self, String> {
+        let value: serde_json::Value =
+            serde_yaml::from_str(yaml).map_err(|e| format!("YAML error: {}", e))?;
+        Self::from_json_str(&value.to_string())
+    }
+
+    /// Loads a single file, choosing the format by its extension
+    fn load_any(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let read = || fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e));
+
+        match extension.as_str() {
+            "json" => Self::load(path.to_path_buf()),
+            "csv" => Self::from_csv_str(&read()?),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Self::from_yaml_str(&read()?),
+            #[cfg(not(feature = "yaml"))]
+            "yaml" | "yml" => Err("YAML support requires the 'yaml' feature".to_string()),
+            other => Err(format!("Unsupported file type '{}'", other)),
+        }
+    }
+
+    /// Loads every JSON, CSV and YAML file in `dir` (not recursive) and merges them in
+    /// filename order, recording each file name as `source_file` in the metadata. Files
+    /// that fail to load are reported without aborting the others; other files are ignored.
+    pub fn load_dir(dir: PathBuf) -> Result<(Self, DirReport), String> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+            .map_err(|e| format!("Failed to read directory: {}", e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path.extension().is_some_and(|ext| {
+                        ["json", "csv", "yaml", "yml"]
+                            .iter()
+                            .any(|known| ext.eq_ignore_ascii_case(known))
+                    })
+            })
+            .collect();
+        files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+        let mut merged = Self::new();
+        let mut report = Vec::with_capacity(files.len());
+        for path in files {
+            let result = Self::load_any(&path).map(|mut kb| {
+                kb.tag_source(&path);
+                merged.merge(&kb);
+                kb.examples.len()
+            });
+            report.push((path, result));
+        }
+
+        Ok((merged, report))
+    }
+
+    /// Loads a knowledge base from a JSON file, failing if it was saved by another version
+    pub fn load_strict(path: PathBuf) -> Result<Self, String> {
+        Self::load_checked(path, true)
+    }
+
+    fn load_checked(path: PathBuf, strict: bool) -> Result<Self, String> {
+        let data = fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+
+        let (examples, version) = parse_training_data(strip_bom(&data))?;
+        if let Some(version) = version {
+            check_version(&version, strict)?;
         }
+
+        Ok(Self {
+            examples,
+            file_path: Some(path),
+        })
     }
 
-    /// Saves the knowledge base to a JSON file
+    /// Parses a knowledge base from a JSON string in any supported format
+    pub fn from_json_str(json: &str) -> Result<Self, String> {
+        let (examples, version) = parse_training_data(json)?;
+        if let Some(version) = version {
+            check_version(&version, false)?;
+        }
+
+        Ok(Self {
+            examples,
+            file_path: None,
+        })
+    }
+
+    /// Serializes the knowledge base to a JSON string in the same layout as `save`
+    pub fn to_json_str(&self) -> Result<String, String> {
+        let data = VersionedTrainingData {
+            version: crate::VERSION,
+            examples: &self.examples,
+        };
+        serde_json::to_string_pretty(&data).map_err(|e| format!("Serialization error: {}", e))
+    }
+
+    /// Writes the compile-time embedded examples to a JSON file, e.g. as a starting point
+    /// for a customized knowledge base
+    pub fn export_embedded(path: PathBuf) -> Result<(), String> {
+        Self::from_embedded().save(Some(path))
+    }
+
+    /// Saves the knowledge base to a JSON file, stamped with the crate version
     pub fn save(&self, path: Option<PathBuf>) -> Result<(), String> {
         let path = path
             .or_else(|| self.file_path.clone())
             .ok_or_else(|| "No path provided".to_string())?;
 
-        let json = serde_json::to_string_pretty(&self.examples)
-            .map_err(|e| format!("Serialization error: {}", e))?;
+        let json = self.to_json_str()?;
 
         let mut file = File::create(&path).map_err(|e| format!("Failed to create file: {}", e))?;
 
@@ -93,6 +572,21 @@ impl KnowledgeBase {
         Ok(())
     }
 
+    /// Saves the knowledge base as a plain legacy array of `{input, output, weight}`.
+    /// Markdown and JSON outputs are written as strings.
+    pub fn save_legacy(&self, path: PathBuf) -> Result<(), String> {
+        let legacy: Vec<LegacyTrainingExample> = self
+            .examples
+            .iter()
+            .filter_map(|ex| ex.to_legacy(true))
+            .collect();
+
+        let json = serde_json::to_string_pretty(&legacy)
+            .map_err(|e| format!("Serialization error: {}", e))?;
+
+        fs::write(&path, json).map_err(|e| format!("Failed to write to file: {}", e))
+    }
+
     /// Adds a new training example to the knowledge base
     pub fn add_example(&mut self, input: String, output: impl Into<ResponseFormat>, weight: f32) {
         let example = TrainingExample {
@@ -113,6 +607,281 @@ impl KnowledgeBase {
         }
     }
 
+    /// Ranks the examples against `query` with BM25 and returns up to `n` (index, score)
+    /// pairs, best first. `tokenizer` turns inputs and query into terms; pass an agent's
+    /// preprocessing (e.g. `|t| agent.preprocess(t)`) to get the same ranking as that agent.
+    /// Defaults to `text_utils::tokenize`. With `weighted`, scores are multiplied by the
+    /// example weights like `TfidfAgent::predict` does; otherwise only relevance counts.
+    pub fn search(
+        &self,
+        query: &str,
+        n: usize,
+        tokenizer: Option<&Tokenizer<'_>>,
+        weighted: bool,
+    ) -> Vec<(usize, f32)> {
+        let tokenize = |text: &str| match tokenizer {
+            Some(tokenizer) => tokenizer(text),
+            None => text_utils::tokenize(text),
+        };
+
+        let weight_mode = if weighted {
+            WeightMode::Multiplicative
+        } else {
+            WeightMode::Ignore
+        };
+        let mut index = TfidfAgent::new().with_weight_mode(weight_mode);
+        for example in &self.examples {
+            index.index_terms(example.clone(), tokenize(&example.input));
+        }
+
+        let mut ranked = index.rank_terms(&tokenize(query));
+        ranked.truncate(n);
+        ranked
+    }
+
+    /// Suggests up to `n` stored questions for a (partial) query, e.g. for autocompletion,
+    /// as `(index, question, score)` with the best first. Questions starting with the
+    /// query score highest (0.5 - 1.0), then questions containing it (0.25 - 0.5), then
+    /// questions whose beginning is a close fuzzy match (below 0.5). Identical questions
+    /// are reported once.
+    pub fn suggest_questions(&self, query: &str, n: usize) -> Vec<(usize, String, f32)> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let query_len = query.chars().count();
+
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut suggestions: Vec<(usize, String, f32)> = Vec::new();
+
+        for (idx, example) in self.examples.iter().enumerate() {
+            let question = example.input.to_lowercase();
+            if !seen.insert(question.clone()) {
+                continue;
+            }
+
+            let coverage = query_len as f32 / question.chars().count().max(query_len) as f32;
+            let score = if question.starts_with(&query) {
+                0.5 + 0.5 * coverage
+            } else if question.contains(&query) {
+                0.25 + 0.25 * coverage
+            } else {
+                let beginning: String = question.chars().take(query_len).collect();
+                let distance = text_utils::normalized_levenshtein(&query, &beginning);
+                if distance < 0.5 {
+                    0.5 * (1.0 - distance)
+                } else {
+                    0.0
+                }
+            };
+
+            if score > 0.0 {
+                suggestions.push((idx, example.input.clone(), score));
+            }
+        }
+
+        suggestions.sort_by(|a, b| {
+            b.2.partial_cmp(&a.2)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        suggestions.truncate(n);
+        suggestions
+    }
+
+    /// Finds pairs of examples whose inputs have a Jaccard similarity (over token sets)
+    /// of at least `threshold`, most similar first, as `(first, second, similarity)`
+    /// with `first < second`. Only examples sharing a token are compared, so pairs
+    /// without any common token are never reported.
+    pub fn find_similar_pairs(&self, threshold: f32) -> Vec<(usize, usize, f32)> {
+        let token_sets: Vec<HashSet<String>> = self
+            .examples
+            .iter()
+            .map(|example| text_utils::unique_terms(&example.input))
+            .collect();
+
+        // Inverted index: token -> examples containing it
+        let mut postings: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (idx, tokens) in token_sets.iter().enumerate() {
+            for token in tokens {
+                postings.entry(token.as_str()).or_default().push(idx);
+            }
+        }
+
+        let mut candidates: HashSet<(usize, usize)> = HashSet::new();
+        for ids in postings.values() {
+            for (pos, &first) in ids.iter().enumerate() {
+                for &second in &ids[pos + 1..] {
+                    candidates.insert((first, second));
+                }
+            }
+        }
+
+        let mut pairs: Vec<(usize, usize, f32)> = candidates
+            .into_iter()
+            .filter_map(|(first, second)| {
+                let (a, b) = (&token_sets[first], &token_sets[second]);
+                let similarity = a.intersection(b).count() as f32 / a.union(b).count() as f32;
+                (similarity >= threshold).then_some((first, second, similarity))
+            })
+            .collect();
+
+        pairs.sort_by(|a, b| {
+            b.2.partial_cmp(&a.2)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| (a.0, a.1).cmp(&(b.0, b.1)))
+        });
+        pairs
+    }
+
+    /// Adds variants of every input derived with the transformations enabled in `options`,
+    /// with the same output and weight, to improve recall of fuzzy and TF-IDF matching.
+    /// Variants are tagged with `"augmented": true` in their metadata; variants that are
+    /// empty or equal an existing input are skipped. Returns the number of added examples.
+    pub fn augment_inputs(&mut self, options: &AugmentOptions) -> usize {
+        let mut known: HashSet<String> = self.examples.iter().map(|e| e.input.clone()).collect();
+        let mut added = Vec::new();
+
+        for example in &self.examples {
+            let is_augmented = example
+                .metadata
+                .as_ref()
+                .and_then(|m| m.get(AUGMENTED_METADATA_KEY))
+                .is_some_and(|flag| flag.as_bool() == Some(true));
+            if is_augmented {
+                continue;
+            }
+
+            let mut variants = Vec::new();
+            if options.lowercase {
+                variants.push(example.input.to_lowercase());
+            }
+            if options.strip_punctuation {
+                let stripped: String = example
+                    .input
+                    .chars()
+                    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+                    .collect();
+                variants.push(stripped.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+            if options.remove_stopwords {
+                let tokens = text_utils::tokenize(&example.input);
+                variants
+                    .push(text_utils::remove_stopwords(tokens, &options.stopword_lang).join(" "));
+            }
+
+            for variant in variants {
+                if variant.trim().is_empty() || !known.insert(variant.clone()) {
+                    continue;
+                }
+
+                let mut metadata = match &example.metadata {
+                    Some(serde_json::Value::Object(map)) => map.clone(),
+                    _ => serde_json::Map::new(),
+                };
+                metadata.insert(
+                    AUGMENTED_METADATA_KEY.to_string(),
+                    serde_json::Value::Bool(true),
+                );
+
+                added.push(TrainingExample {
+                    input: variant,
+                    output: example.output.clone(),
+                    weight: example.weight,
+                    metadata: Some(serde_json::Value::Object(metadata)),
+                });
+            }
+        }
+
+        let count = added.len();
+        self.examples.extend(added);
+        count
+    }
+
+    /// Removes examples whose input and output both equal those of an earlier example,
+    /// keeping the first one with its weight and metadata. Examples sharing only the
+    /// input or only the output are kept. Returns the number of removed examples.
+    pub fn dedup_exact(&mut self) -> usize {
+        let before = self.examples.len();
+        let mut kept_by_input: HashMap<String, Vec<ResponseFormat>> = HashMap::new();
+
+        self.examples.retain(|example| {
+            let outputs = kept_by_input.entry(example.input.clone()).or_default();
+            if outputs.contains(&example.output) {
+                false
+            } else {
+                outputs.push(example.output.clone());
+                true
+            }
+        });
+
+        before - self.examples.len()
+    }
+
+    /// Sorts the examples by weight, highest first if `descending`. The sort is stable,
+    /// so examples with equal weight keep their relative order.
+    pub fn sort_by_weight(&mut self, descending: bool) {
+        if descending {
+            self.examples.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+        } else {
+            self.examples.sort_by(|a, b| a.weight.total_cmp(&b.weight));
+        }
+    }
+
+    /// Sorts the examples by input, e.g. to get stable diffs of saved files after merging.
+    /// The sort is stable, so examples with equal input keep their relative order.
+    pub fn sort_by_input(&mut self) {
+        self.examples.sort_by(|a, b| a.input.cmp(&b.input));
+    }
+
+    /// Computes a stable hash over input, output (with its variant) and weight of all
+    /// examples, e.g. to detect that a cached index no longer matches the knowledge base.
+    /// The hash depends on the example order; call `sort_by_input` first to compare
+    /// bases regardless of order. Metadata and the file path are not included.
+    pub fn fingerprint(&self) -> u64 {
+        let mut content = String::new();
+        for example in &self.examples {
+            content.push_str(&format!(
+                "{}\u{1f}{}\u{1f}{}\u{1f}{}\u{1e}",
+                example.input,
+                example.output.variant_name(),
+                example.output,
+                example.weight.to_bits()
+            ));
+        }
+        text_utils::stable_hash(&content)
+    }
+
+    /// Returns a new knowledge base with copies of the examples at `indices`, in that
+    /// order, e.g. to build a focused test set. Indices out of range are skipped.
+    /// The new base has no file path.
+    pub fn subset(&self, indices: &[usize]) -> KnowledgeBase {
+        KnowledgeBase {
+            examples: indices
+                .iter()
+                .filter_map(|&idx| self.examples.get(idx).cloned())
+                .collect(),
+            file_path: None,
+        }
+    }
+
+    /// Returns a new knowledge base with copies of the examples matching `predicate`,
+    /// in their original order. The new base has no file path.
+    pub fn subset_where<F>(&self, predicate: F) -> KnowledgeBase
+    where
+        F: Fn(&TrainingExample) -> bool,
+    {
+        KnowledgeBase {
+            examples: self
+                .examples
+                .iter()
+                .filter(|example| predicate(example))
+                .cloned()
+                .collect(),
+            file_path: None,
+        }
+    }
+
     /// Returns a reference to all training examples
     pub fn get_examples(&self) -> &[TrainingExample] {
         &self.examples
@@ -135,3 +904,700 @@ impl Default for KnowledgeBase {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("airust_{}_{}.json", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_save_stamps_version() {
+        let path = temp_path("kb_version_stamp");
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("hello".to_string(), "world", 1.0);
+        kb.save(Some(path.clone())).unwrap();
+
+        let raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw["version"], crate::VERSION);
+
+        let loaded = KnowledgeBase::load_strict(path.clone()).unwrap();
+        assert_eq!(loaded.get_examples().len(), 1);
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_load_strips_bom_and_lenient_trailing_commas() {
+        let path = temp_path("kb_bom");
+        fs::write(
+            &path,
+            "\u{feff}[{\"input\": \"What is airust?\", \"output\": {\"Text\": \"A Rust library\"}}]",
+        )
+        .unwrap();
+        let kb = KnowledgeBase::load(path.clone()).unwrap();
+        assert_eq!(kb.get_examples()[0].input, "What is airust?");
+        assert!(KnowledgeBase::validate_file(path.clone()).is_ok());
+
+        fs::write(
+            &path,
+            "\u{feff}[\n  {\"input\": \"a, ]\", \"output\": {\"Text\": \"b\"},},\n]\n",
+        )
+        .unwrap();
+        assert!(KnowledgeBase::load(path.clone()).is_err());
+        let kb = KnowledgeBase::load_lenient(path.clone()).unwrap();
+        assert_eq!(kb.get_examples().len(), 1);
+        // Commas inside strings are kept
+        assert_eq!(kb.get_examples()[0].input, "a, ]");
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_subset() {
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("What is airust?".to_string(), "A Rust library", 1.0);
+        kb.add_example("What is BM25?".to_string(), "A ranking function", 2.5);
+        kb.add_example("Who wrote it?".to_string(), "The maintainers", 0.5);
+        kb.examples[1].metadata = Some(serde_json::json!({"domain": "search"}));
+
+        let subset = kb.subset(&[2, 1, 7]);
+        let inputs: Vec<&str> = subset
+            .get_examples()
+            .iter()
+            .map(|e| e.input.as_str())
+            .collect();
+        assert_eq!(inputs, vec!["Who wrote it?", "What is BM25?"]);
+        assert_eq!(subset.get_examples()[0].weight, 0.5);
+        assert_eq!(subset.get_examples()[1].weight, 2.5);
+        assert_eq!(
+            subset.get_examples()[1].metadata,
+            Some(serde_json::json!({"domain": "search"}))
+        );
+
+        let search = kb.subset_where(|example| {
+            example
+                .metadata
+                .as_ref()
+                .is_some_and(|m| m["domain"] == "search")
+        });
+        assert_eq!(search.get_examples().len(), 1);
+        assert_eq!(search.get_examples()[0].input, "What is BM25?");
+        assert_eq!(search.fingerprint(), kb.subset(&[1]).fingerprint());
+
+        // The original is unchanged
+        assert_eq!(kb.get_examples().len(), 3);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("What is airust?".to_string(), "A Rust library", 1.0);
+        kb.add_example("What is BM25?".to_string(), "A ranking function", 1.0);
+
+        let mut same = KnowledgeBase::new();
+        same.add_example("What is BM25?".to_string(), "A ranking function", 1.0);
+        same.add_example("What is airust?".to_string(), "A Rust library", 1.0);
+        assert_ne!(kb.fingerprint(), same.fingerprint());
+        kb.sort_by_input();
+        same.sort_by_input();
+        assert_eq!(kb.fingerprint(), same.fingerprint());
+        assert_eq!(kb.fingerprint(), kb.clone().fingerprint());
+
+        let mut edited = kb.clone();
+        edited.examples[0].weight = 2.0;
+        assert_ne!(edited.fingerprint(), kb.fingerprint());
+
+        let mut edited = kb.clone();
+        edited.examples[1].output = ResponseFormat::Markdown("A Rust library".to_string());
+        assert_ne!(edited.fingerprint(), kb.fingerprint());
+    }
+
+    #[test]
+    fn test_search_matches_configured_agent() {
+        use crate::agent::TrainableAgent;
+
+        let mut kb = KnowledgeBase::new();
+        for (input, output) in [
+            ("what is the capital of france", "Paris"),
+            ("the history of the roman empire", "Rome"),
+            ("capital letters in the alphabet", "ABC"),
+            ("what is the weather in paris", "Rain"),
+        ] {
+            kb.add_example(
+                input.to_string(),
+                ResponseFormat::Text(output.to_string()),
+                1.0,
+            );
+        }
+
+        let mut agent = TfidfAgent::new().with_stopword_removal(true);
+        agent.train(kb.get_examples());
+
+        let query = "the capital of the empire";
+        let pipeline = |text: &str| agent.preprocess(text);
+        let searched: Vec<usize> = kb
+            .search(query, 10, Some(&pipeline), true)
+            .into_iter()
+            .map(|(idx, _)| idx)
+            .collect();
+        let predicted: Vec<usize> = agent
+            .predict_above(query, 0.0)
+            .iter()
+            .map(|r| {
+                r.metadata.as_ref().unwrap()["example_index"]
+                    .as_u64()
+                    .unwrap() as usize
+            })
+            .collect();
+        assert_eq!(searched, predicted);
+        assert_eq!(searched.len(), 3);
+
+        // The default tokenizer keeps stopwords, so every example matches
+        assert_eq!(kb.search(query, 10, None, true).len(), 4);
+        assert_eq!(kb.search(query, 2, None, true).len(), 2);
+    }
+
+    #[test]
+    fn test_search_weighted() {
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("rust ownership rules".to_string(), "a", 1.0);
+        kb.add_example("rust ownership guide".to_string(), "b", 5.0);
+        kb.add_example("python typing".to_string(), "c", 1.0);
+
+        let order = |weighted: bool| -> Vec<usize> {
+            kb.search("rust ownership rules", 10, None, weighted)
+                .into_iter()
+                .map(|(idx, _)| idx)
+                .collect()
+        };
+        assert_eq!(order(false), vec![0, 1]);
+        assert_eq!(order(true), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_modern_format_takes_precedence() {
+        let modern = r#"[{"input": "q", "output": {"Text": "a"}, "metadata": {"page": 7}}]"#;
+        let kb = KnowledgeBase::from_json_str(modern).unwrap();
+        let example = &kb.get_examples()[0];
+        assert_eq!(String::from(example.output.clone()), "a");
+        assert_eq!(example.metadata.as_ref().unwrap()["page"], 7);
+
+        let legacy = r#"[{"input": "q", "output": "a", "weight": 2.0}]"#;
+        let kb = KnowledgeBase::from_json_str(legacy).unwrap();
+        let example = &kb.get_examples()[0];
+        assert_eq!(String::from(example.output.clone()), "a");
+        assert_eq!(example.weight, 2.0);
+
+        // An empty array is read as modern and yields an empty base
+        assert!(KnowledgeBase::from_json_str("[]")
+            .unwrap()
+            .get_examples()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_export_embedded_round_trip() {
+        let path = temp_path("kb_export_embedded");
+        KnowledgeBase::export_embedded(path.clone()).unwrap();
+
+        let reloaded = KnowledgeBase::load(path.clone()).unwrap();
+        assert_eq!(
+            reloaded.to_json_str().unwrap(),
+            KnowledgeBase::from_embedded().to_json_str().unwrap()
+        );
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_from_csv_str() {
+        let csv = "Output,input,weight\r\n\"A library, in Rust\",What is airust?,2\n\n\"Says \"\"hi\"\"\",\"Multi\nline\",\n";
+        let kb = KnowledgeBase::from_csv_str(csv).unwrap();
+        let examples = kb.get_examples();
+        assert_eq!(examples.len(), 2);
+        assert_eq!(examples[0].input, "What is airust?");
+        assert_eq!(examples[0].output.to_string(), "A library, in Rust");
+        assert_eq!(examples[0].weight, 2.0);
+        assert_eq!(examples[1].input, "Multi\nline");
+        assert_eq!(examples[1].output.to_string(), "Says \"hi\"");
+        assert_eq!(examples[1].weight, 1.0);
+
+        assert!(KnowledgeBase::from_csv_str("question,answer\nq,a").is_err());
+        assert!(KnowledgeBase::from_csv_str("input,output\nq").is_err());
+        assert!(KnowledgeBase::from_csv_str("input,output,weight\nq,a,heavy").is_err());
+        assert!(KnowledgeBase::from_csv_str("input,output\n\"q,a").is_err());
+    }
+
+    #[test]
+    fn test_load_dir_merges_json_and_csv() {
+        let dir = std::env::temp_dir().join(format!("airust_load_dir_csv_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(
+            dir.join("a.json"),
+            r#"[{"input": "What is airust?", "output": {"Text": "A Rust library"}}]"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.CSV"),
+            "input,output,weight\nWhat is BM25?,A ranking function,1.5\nWho wrote it?,The maintainers,1\n",
+        )
+        .unwrap();
+        fs::write(dir.join("c.csv"), "question,answer\nq,a\n").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let (kb, report) = KnowledgeBase::load_dir(dir.clone()).unwrap();
+        let inputs: Vec<&str> = kb.get_examples().iter().map(|e| e.input.as_str()).collect();
+        assert_eq!(
+            inputs,
+            vec!["What is airust?", "What is BM25?", "Who wrote it?"]
+        );
+        assert_eq!(kb.get_examples()[1].weight, 1.5);
+        assert_eq!(
+            kb.get_examples()[0].metadata.as_ref().unwrap()["source_file"],
+            "a.json"
+        );
+        assert_eq!(
+            kb.get_examples()[2].metadata.as_ref().unwrap()["source_file"],
+            "b.CSV"
+        );
+
+        assert_eq!(report.len(), 3);
+        assert_eq!(report[0].1, Ok(1));
+        assert_eq!(report[1].1, Ok(2));
+        assert!(report[2].1.is_err());
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_load_dir_merges_mixed_formats() {
+        let dir = std::env::temp_dir().join(format!("airust_load_dir_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(
+            dir.join("a.json"),
+            r#"[{"input": "What is airust?", "output": {"Text": "A Rust library"}}]"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.csv"),
+            "input,output,weight\nWhat is BM25?,A ranking function,1.5\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("c.yaml"),
+            "- input: Who wrote it?\n  output:\n    Markdown: The **maintainers**\n",
+        )
+        .unwrap();
+        fs::write(dir.join("d.yml"), "not: [valid").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let (kb, report) = KnowledgeBase::load_dir(dir.clone()).unwrap();
+        let inputs: Vec<&str> = kb.get_examples().iter().map(|e| e.input.as_str()).collect();
+        assert_eq!(
+            inputs,
+            vec!["What is airust?", "What is BM25?", "Who wrote it?"]
+        );
+        assert_eq!(kb.get_examples()[1].weight, 1.5);
+        assert!(matches!(
+            kb.get_examples()[2].output,
+            ResponseFormat::Markdown(_)
+        ));
+        assert_eq!(
+            kb.get_examples()[1].metadata.as_ref().unwrap()["source_file"],
+            "b.csv"
+        );
+
+        assert_eq!(report.len(), 4);
+        assert!(report[..3].iter().all(|(_, result)| *result == Ok(1)));
+        assert!(report[3].1.is_err());
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    #[test]
+    fn test_load_dir_reports_yaml_without_feature() {
+        let dir = std::env::temp_dir().join(format!("airust_no_yaml_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(
+            dir.join("a.json"),
+            r#"[{"input": "What is airust?", "output": {"Text": "A Rust library"}}]"#,
+        )
+        .unwrap();
+        fs::write(dir.join("b.yaml"), "- input: Who wrote it?\n").unwrap();
+
+        let (kb, report) = KnowledgeBase::load_dir(dir.clone()).unwrap();
+        assert_eq!(kb.get_examples().len(), 1);
+        assert_eq!(report.len(), 2);
+        assert_eq!(
+            report[1].1,
+            Err("YAML support requires the 'yaml' feature".to_string())
+        );
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_dedup_exact() {
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("What is airust?".to_string(), "A Rust library", 2.0);
+        kb.add_example("What is airust?".to_string(), "A Rust library", 1.0);
+        kb.add_example("What is airust?".to_string(), "An AI engine", 1.0);
+        kb.add_example("Tell me about airust".to_string(), "A Rust library", 1.0);
+        kb.add_example(
+            "What is airust?".to_string(),
+            ResponseFormat::Markdown("A Rust library".to_string()),
+            1.0,
+        );
+        kb.examples[0].metadata = Some(serde_json::json!({"source_file": "first.json"}));
+
+        assert_eq!(kb.dedup_exact(), 1);
+        let pairs: Vec<(&str, String)> = kb
+            .get_examples()
+            .iter()
+            .map(|e| (e.input.as_str(), e.output.to_string()))
+            .collect();
+        // Same input with another answer, same answer for another input and the same
+        // text in another format are all kept
+        assert_eq!(pairs.len(), 4);
+        assert_eq!(pairs[1], ("What is airust?", "An AI engine".to_string()));
+        assert_eq!(kb.get_examples()[0].weight, 2.0);
+        assert_eq!(
+            kb.get_examples()[0].metadata.as_ref().unwrap()["source_file"],
+            "first.json"
+        );
+
+        assert_eq!(kb.dedup_exact(), 0);
+    }
+
+    #[test]
+    fn test_augment_inputs() {
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("Reset the Router!".to_string(), "Hold the button", 1.5);
+        kb.add_example("reset router".to_string(), "Duplicate target", 1.0);
+
+        let added = kb.augment_inputs(&AugmentOptions::default());
+        let inputs: Vec<&str> = kb.get_examples().iter().map(|e| e.input.as_str()).collect();
+        // "reset router" (stopwords removed) already exists and is not added again
+        assert_eq!(added, 2);
+        assert_eq!(
+            inputs,
+            vec![
+                "Reset the Router!",
+                "reset router",
+                "reset the router!",
+                "Reset the Router",
+            ]
+        );
+
+        for example in &kb.get_examples()[2..] {
+            assert_eq!(example.metadata.as_ref().unwrap()["augmented"], true);
+            assert_eq!(String::from(example.output.clone()), "Hold the button");
+            assert_eq!(example.weight, 1.5);
+        }
+        assert!(kb.get_examples()[0].metadata.is_none());
+
+        // Running again adds nothing new
+        assert_eq!(kb.augment_inputs(&AugmentOptions::default()), 0);
+
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("What is BM25?".to_string(), "A ranking function", 1.0);
+        let only_lowercase = AugmentOptions {
+            strip_punctuation: false,
+            remove_stopwords: false,
+            ..Default::default()
+        };
+        assert_eq!(kb.augment_inputs(&only_lowercase), 1);
+        assert_eq!(kb.get_examples()[1].input, "what is bm25?");
+    }
+
+    #[test]
+    fn test_sort_by_weight_and_input() {
+        let entries = [
+            ("b", "first", 1.0),
+            ("a", "x", 2.0),
+            ("b", "second", 1.0),
+            ("c", "y", 0.5),
+        ];
+        let mut kb = KnowledgeBase::new();
+        for (input, output, weight) in entries {
+            kb.add_example(input.to_string(), output, weight);
+        }
+        let outputs = |kb: &KnowledgeBase| -> Vec<String> {
+            kb.get_examples()
+                .iter()
+                .map(|e| e.output.to_string())
+                .collect()
+        };
+
+        kb.sort_by_weight(true);
+        assert_eq!(outputs(&kb), vec!["x", "first", "second", "y"]);
+        kb.sort_by_weight(false);
+        assert_eq!(outputs(&kb), vec!["y", "first", "second", "x"]);
+        // Equal inputs keep their relative order
+        kb.sort_by_input();
+        assert_eq!(outputs(&kb), vec!["x", "first", "second", "y"]);
+
+        // Merge order no longer shows up in the saved file
+        let mut left = KnowledgeBase::new();
+        left.add_example("q1".to_string(), "a1", 1.0);
+        let mut right = KnowledgeBase::new();
+        right.add_example("q2".to_string(), "a2", 1.0);
+        let mut merged_lr = KnowledgeBase::new();
+        merged_lr.merge(&left);
+        merged_lr.merge(&right);
+        let mut merged_rl = KnowledgeBase::new();
+        merged_rl.merge(&right);
+        merged_rl.merge(&left);
+        merged_lr.sort_by_input();
+        merged_rl.sort_by_input();
+        assert_eq!(merged_lr.to_json_str(), merged_rl.to_json_str());
+    }
+
+    #[test]
+    fn test_suggest_questions() {
+        let mut kb = KnowledgeBase::new();
+        for input in [
+            "How do I reset my password?",
+            "How do I change my email address?",
+            "Where can I reset the router?",
+            "How do I reset my password?",
+            "What are the opening hours?",
+        ] {
+            kb.add_example(
+                input.to_string(),
+                ResponseFormat::Text("a".to_string()),
+                1.0,
+            );
+        }
+
+        let suggestions = kb.suggest_questions("how do i", 5);
+        let questions: Vec<&str> = suggestions.iter().map(|(_, q, _)| q.as_str()).collect();
+        // Shorter questions are covered better by the prefix; duplicates are dropped
+        assert_eq!(
+            questions,
+            vec![
+                "How do I reset my password?",
+                "How do I change my email address?"
+            ]
+        );
+        assert_eq!(suggestions[0].0, 0);
+
+        // Prefix matches rank above questions merely containing the query
+        let suggestions = kb.suggest_questions("how do i reset", 3);
+        assert_eq!(suggestions[0].1, "How do I reset my password?");
+        let suggestions = kb.suggest_questions("reset", 3);
+        assert_eq!(suggestions.len(), 2);
+        assert!(suggestions.iter().all(|(_, _, score)| *score < 0.5));
+
+        // Typos in the beginning are tolerated
+        let suggestions = kb.suggest_questions("waht are", 3);
+        assert_eq!(suggestions[0].1, "What are the opening hours?");
+
+        assert_eq!(kb.suggest_questions("how", 1).len(), 1);
+        assert!(kb.suggest_questions("  ", 3).is_empty());
+    }
+
+    #[test]
+    fn test_find_similar_pairs() {
+        let mut kb = KnowledgeBase::new();
+        for input in [
+            "How do I reset my password?",
+            "What are the opening hours?",
+            "how do i reset my password",
+            "Where is the nearest store?",
+            "How do I change my email?",
+        ] {
+            kb.add_example(
+                input.to_string(),
+                ResponseFormat::Text("a".to_string()),
+                1.0,
+            );
+        }
+
+        let pairs = kb.find_similar_pairs(0.8);
+        assert_eq!(pairs, vec![(0, 2, 1.0)]);
+
+        // A lower threshold also reports the partially overlapping question
+        let pairs = kb.find_similar_pairs(0.4);
+        assert_eq!(pairs[0], (0, 2, 1.0));
+        assert!(pairs.iter().any(|&(a, b, _)| (a, b) == (0, 4)));
+        assert!(pairs.iter().all(|&(a, b, s)| a < b && s >= 0.4));
+
+        assert!(KnowledgeBase::new().find_similar_pairs(0.5).is_empty());
+    }
+
+    #[test]
+    fn test_validate_file_reports_all_errors() {
+        let path = temp_path("kb_validate");
+        fs::write(
+            &path,
+            r#"[
+                {"input": "ok", "output": "legacy"},
+                {"output": {"Text": "no input"}},
+                {"input": "a", "output": 42},
+                {"input": "b", "output": {"Text": "x"}, "weight": "high"},
+                {"input": 7, "output": {"Html": "<p>"}},
+                {"input": "c", "output": {"Json": {"k": 1}}, "weight": 2}
+            ]"#,
+        )
+        .unwrap();
+
+        let errors = KnowledgeBase::validate_file(path.clone()).unwrap_err();
+        let found: Vec<(Option<usize>, &str)> =
+            errors.iter().map(|e| (e.index, e.field.as_str())).collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(1), "input"),
+                (Some(2), "output"),
+                (Some(3), "weight"),
+                (Some(4), "input"),
+                (Some(4), "output"),
+            ]
+        );
+        assert_eq!(errors[0].to_string(), "example 1: input: is missing");
+
+        // Files written by save() pass
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("q".to_string(), ResponseFormat::Text("a".to_string()), 1.0);
+        kb.save(Some(path.clone())).unwrap();
+        assert!(KnowledgeBase::validate_file(path.clone()).is_ok());
+
+        fs::write(&path, "not json").unwrap();
+        let errors = KnowledgeBase::validate_file(path.clone()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].index, None);
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_merge_preserves_source_file() {
+        let first = temp_path("kb_source_first");
+        let second = temp_path("kb_source_second");
+        fs::write(&first, r#"[{"input": "a", "output": "1"}]"#).unwrap();
+        fs::write(
+            &second,
+            r#"[{"input": "b", "output": {"Text": "2"}, "metadata": {"page": 3}}]"#,
+        )
+        .unwrap();
+
+        let mut merged = KnowledgeBase::new();
+        merged.merge(&KnowledgeBase::load_with_source(first.clone()).unwrap());
+        merged.merge(&KnowledgeBase::load_with_source(second.clone()).unwrap());
+
+        let sources: Vec<&serde_json::Value> = merged
+            .get_examples()
+            .iter()
+            .map(|ex| &ex.metadata.as_ref().unwrap()["source_file"])
+            .collect();
+        assert_eq!(
+            sources[0],
+            &serde_json::json!(first.file_name().unwrap().to_str())
+        );
+        assert_eq!(
+            sources[1],
+            &serde_json::json!(second.file_name().unwrap().to_str())
+        );
+
+        // Existing metadata is kept
+        assert_eq!(
+            merged.get_examples()[1].metadata.as_ref().unwrap()["page"],
+            3
+        );
+
+        fs::remove_file(first).ok();
+        fs::remove_file(second).ok();
+    }
+
+    #[test]
+    fn test_json_str_round_trip() {
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("question".to_string(), "answer", 2.0);
+        kb.add_example(
+            "data".to_string(),
+            ResponseFormat::Json(serde_json::json!({"id": 7})),
+            1.0,
+        );
+
+        let json = kb.to_json_str().unwrap();
+        let restored = KnowledgeBase::from_json_str(&json).unwrap();
+        assert_eq!(restored.to_json_str().unwrap(), json);
+        assert_eq!(restored.get_examples()[0].weight, 2.0);
+
+        let legacy =
+            KnowledgeBase::from_json_str(r#"[{"input": "hi", "output": "there"}]"#).unwrap();
+        assert_eq!(legacy.get_examples()[0].input, "hi");
+        assert_eq!(
+            String::from(legacy.get_examples()[0].output.clone()),
+            "there"
+        );
+        assert_eq!(legacy.get_examples()[0].weight, 1.0);
+
+        assert!(KnowledgeBase::from_json_str("{not json").is_err());
+    }
+
+    #[test]
+    fn test_save_legacy() {
+        let path = temp_path("kb_legacy");
+        let mut kb = KnowledgeBase::new();
+        kb.add_example("greeting".to_string(), "hello", 1.5);
+        kb.add_example(
+            "config".to_string(),
+            ResponseFormat::Json(serde_json::json!({"debug": true})),
+            1.0,
+        );
+        kb.save_legacy(path.clone()).unwrap();
+
+        let raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            raw,
+            serde_json::json!([
+                {"input": "greeting", "output": "hello", "weight": 1.5},
+                {"input": "config", "output": "{\"debug\":true}", "weight": 1.0}
+            ])
+        );
+
+        // The legacy file still loads as a knowledge base
+        let loaded = KnowledgeBase::load(path.clone()).unwrap();
+        assert_eq!(
+            String::from(loaded.get_examples()[0].output.clone()),
+            "hello"
+        );
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_load_version_mismatch() {
+        let path = temp_path("kb_version_mismatch");
+        fs::write(
+            &path,
+            r#"{"version": "0.0.0", "examples": [{"input": "hi", "output": {"Text": "there"}}]}"#,
+        )
+        .unwrap();
+
+        let err = KnowledgeBase::load_strict(path.clone()).err().unwrap();
+        assert!(err.contains("0.0.0"));
+        assert!(err.contains(crate::VERSION));
+
+        // Non-strict loading only warns
+        let kb = KnowledgeBase::load(path.clone()).unwrap();
+        assert_eq!(kb.get_examples().len(), 1);
+
+        fs::remove_file(path).ok();
+    }
+}
@@ -2,19 +2,35 @@
 
 // Core modules
 pub mod agent;
+pub mod const_agent;
 pub mod context_agent;
+pub mod eval;
 pub mod knowledge;
+pub mod map_agent;
 pub mod match_agent;
 pub mod pdf_loader;
+pub mod recording_agent;
+pub mod router_agent;
 pub mod tfidf_agent;
 
 // Re-exports for easier usage
-pub use agent::{Agent, ContextualAgent, ResponseFormat, TrainableAgent, TrainingExample};
-pub use context_agent::ContextAgent;
-pub use knowledge::KnowledgeBase;
+pub use agent::{
+    Agent, ContextualAgent, ResponseFormat, TrainableAgent, TrainableAgentExt, TrainingExample,
+};
+pub use const_agent::ConstAgent;
+pub use context_agent::{ContextAgent, ContextBudget};
+pub use knowledge::{AugmentOptions, KnowledgeBase, SchemaError};
+pub use map_agent::MapAgent;
 pub use match_agent::MatchAgent;
-pub use pdf_loader::{pdf_to_knowledge_base, pdf_to_training_examples, PdfLoader, PdfLoaderConfig};
-pub use tfidf_agent::TfidfAgent;
+pub use pdf_loader::{
+    pdf_to_knowledge_base, pdf_to_training_examples, BatchReport, PdfLoader, PdfLoaderConfig,
+    SentenceSegmenter,
+};
+pub use recording_agent::{RecordedQuery, RecordingAgent};
+pub use router_agent::{RoutePredicate, RouterAgent};
+#[cfg(feature = "timing")]
+pub use tfidf_agent::PredictTiming;
+pub use tfidf_agent::{IdfVariant, IndexStats, ScoringFn, TfidfAgent, TfidfConfig, WeightMode};
 
 // Version and library information
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
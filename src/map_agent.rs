@@ -0,0 +1,90 @@
+// src/map_agent.rs - Agent adapter that post-processes responses
+use crate::agent::{Agent, ResponseFormat, TrainableAgent, TrainingExample};
+
+/// Wraps an agent and passes every predicted response through a mapping function,
+/// e.g. to append a footer or convert markdown to plain text
+pub struct MapAgent<A: Agent, F: Fn(ResponseFormat) -> ResponseFormat> {
+    inner: A,
+    map: F,
+}
+
+impl<A: Agent, F: Fn(ResponseFormat) -> ResponseFormat> MapAgent<A, F> {
+    /// Creates a new map agent around `inner`
+    pub fn new(inner: A, map: F) -> Self {
+        Self { inner, map }
+    }
+
+    /// Returns the wrapped agent
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+}
+
+impl<A: Agent, F: Fn(ResponseFormat) -> ResponseFormat> Agent for MapAgent<A, F> {
+    /// Predicts with the wrapped agent and maps the response
+    fn predict(&self, input: &str) -> ResponseFormat {
+        (self.map)(self.inner.predict(input))
+    }
+
+    /// Delegates the confidence to the wrapped agent unchanged
+    fn confidence(&self, input: &str) -> f32 {
+        self.inner.confidence(input)
+    }
+
+    /// Uses the confidence threshold of the wrapped agent
+    fn confidence_threshold(&self) -> f32 {
+        self.inner.confidence_threshold()
+    }
+
+    /// Returns the inputs known to the wrapped agent
+    fn known_inputs(&self) -> Vec<&str> {
+        self.inner.known_inputs()
+    }
+
+    /// Returns the formats of the wrapped agent
+    fn supported_formats(&self) -> Vec<&'static str> {
+        self.inner.supported_formats()
+    }
+}
+
+impl<A: TrainableAgent, F: Fn(ResponseFormat) -> ResponseFormat> TrainableAgent for MapAgent<A, F> {
+    /// Trains the wrapped agent with the provided training data
+    fn train(&mut self, data: &[TrainingExample]) {
+        self.inner.train(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tfidf_agent::TfidfAgent;
+
+    #[test]
+    fn test_footer_changes_output_not_confidence() {
+        let data = [TrainingExample {
+            input: "What is airust?".to_string(),
+            output: ResponseFormat::Text("A Rust library".to_string()),
+            weight: 1.0,
+            metadata: None,
+        }];
+
+        let mut plain = TfidfAgent::new();
+        plain.train(&data);
+        let expected_confidence = plain.confidence("what is airust");
+
+        let mut agent = MapAgent::new(TfidfAgent::new(), |response: ResponseFormat| {
+            ResponseFormat::Text(format!("{}\n-- Support team", response))
+        });
+        agent.train(&data);
+
+        assert_eq!(
+            String::from(agent.predict("what is airust")),
+            "A Rust library\n-- Support team"
+        );
+        assert_eq!(agent.confidence("what is airust"), expected_confidence);
+        assert_eq!(
+            String::from(agent.inner().predict("what is airust")),
+            "A Rust library"
+        );
+    }
+}
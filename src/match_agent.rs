@@ -1,24 +1,74 @@
 // src/match_agent.rs - Unified matching agent replacing simple and fuzzy agents
-use crate::agent::{Agent, ResponseFormat, TrainableAgent, TrainingExample};
-use strsim::levenshtein;
+use crate::agent::{
+    limit_input, supported_formats_of, text_utils, truncation_metadata, Agent, InputPreprocessor,
+    PredictionResult, ResponseFormat, TrainableAgent, TrainingExample,
+    DEFAULT_CONFIDENCE_THRESHOLD, EMPTY_QUERY_RESPONSE,
+};
+#[cfg(test)]
+use std::cell::Cell;
+use std::collections::HashMap;
 
 /// Defines different matching strategies for finding relevant training examples
 pub enum MatchingStrategy {
-    /// Exact match requiring full equality (case-insensitive)
+    /// Exact match requiring full equality (case-insensitive via `to_lowercase`, or Unicode
+    /// case folding with `with_case_folding(true)`; whitespace and typographic quotes/dashes
+    /// are normalized unless disabled with `with_normalization(false)`)
     Exact,
     /// Fuzzy matching with configurable options
     Fuzzy(FuzzyOptions),
+    /// Substring matching (case-insensitive) selecting the shortest stored input
+    /// that contains the query; when `bidirectional` is set, stored inputs
+    /// contained in the query match as well
+    Contains { bidirectional: bool },
 }
 
-/// Configuration options for fuzzy matching
+/// Configuration options for fuzzy matching.
+/// When both limits are set, a candidate must satisfy both of them.
 pub struct FuzzyOptions {
     /// Maximum allowed Levenshtein distance between input and training example
     /// None means no hard limit on distance
     pub max_distance: Option<usize>,
 
     /// Dynamic threshold factor based on input length
-    /// Scales the maximum allowed distance as a fraction of input length
+    /// Scales the maximum allowed distance as a fraction of input length, counted in
+    /// characters like the distances themselves. Non-ASCII queries therefore get the
+    /// same threshold as ASCII queries of equal length rather than one inflated by
+    /// their UTF-8 byte count.
     pub threshold_factor: Option<f32>,
+
+    /// Edit distance used to compare input and training examples
+    pub algorithm: DistanceAlgorithm,
+
+    /// Stops scanning at the first accepted candidate within this distance, trading
+    /// accuracy for latency on large memories: a closer example later in memory is then
+    /// missed. None only stops at exact (zero-distance) matches, which never changes results.
+    pub good_enough_distance: Option<usize>,
+}
+
+/// Edit distances available for fuzzy matching
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DistanceAlgorithm {
+    /// Insertions, deletions and substitutions
+    #[default]
+    Levenshtein,
+    /// Like Levenshtein, but swapping two adjacent characters counts as one edit,
+    /// which suits typos like "teh" for "the"
+    DamerauLevenshtein,
+}
+
+impl DistanceAlgorithm {
+    /// Computes the distance between two strings
+    fn distance(self, a: &str, b: &str) -> usize {
+        match self {
+            DistanceAlgorithm::Levenshtein => text_utils::levenshtein_distance(a, b),
+            DistanceAlgorithm::DamerauLevenshtein => text_utils::damerau_levenshtein_distance(a, b),
+        }
+    }
+
+    /// Distance divided by the character length of the longer string (0.0 - 1.0)
+    fn normalized(self, a: &str, b: &str) -> f32 {
+        text_utils::normalized_distance(a, b, |a, b| self.distance(a, b))
+    }
 }
 
 /// Default configuration for fuzzy matching
@@ -27,6 +77,8 @@ impl Default for FuzzyOptions {
         Self {
             max_distance: None,
             threshold_factor: Some(0.3), // Default: 30% of input length as max distance
+            algorithm: DistanceAlgorithm::Levenshtein,
+            good_enough_distance: None, // Default: only an exact match ends the scan early
         }
     }
 }
@@ -38,6 +90,13 @@ impl Default for MatchingStrategy {
     }
 }
 
+#[cfg(test)]
+thread_local! {
+    /// Number of stored keys compared against a query by fuzzy and substring scans, to
+    /// observe early exits and index lookups in tests
+    static KEY_COMPARISONS: Cell<usize> = const { Cell::new(0) };
+}
+
 /// Unified agent capable of exact and fuzzy matching
 pub struct MatchAgent {
     /// Stored training examples
@@ -45,6 +104,28 @@ pub struct MatchAgent {
 
     /// Current matching strategy
     strategy: MatchingStrategy,
+
+    /// Preprocessed, lowercased inputs of the stored examples (same order as `memory`)
+    keys: Vec<String>,
+
+    /// Lowercased input -> index of the preferred example, for O(1) exact lookups.
+    /// Only built for the exact strategy.
+    exact_index: HashMap<String, usize>,
+
+    /// Optional normalization applied to stored inputs and queries
+    input_preprocessor: Option<InputPreprocessor>,
+
+    /// Confidence an answer must exceed for `can_answer`
+    confidence_threshold: f32,
+
+    /// Whether exact matching ignores whitespace and typographic quote/dash differences
+    normalize: bool,
+
+    /// Queries longer than this many characters are truncated before matching
+    max_input_chars: Option<usize>,
+
+    /// Whether exact matching compares Unicode case-folded text instead of lowercase text
+    case_folding: bool,
 }
 
 impl MatchAgent {
@@ -53,6 +134,13 @@ impl MatchAgent {
         Self {
             memory: Vec::new(),
             strategy,
+            keys: Vec::new(),
+            exact_index: HashMap::new(),
+            input_preprocessor: None,
+            confidence_threshold: DEFAULT_CONFIDENCE_THRESHOLD,
+            normalize: true,
+            max_input_chars: None,
+            case_folding: false,
         }
     }
 
@@ -66,45 +154,149 @@ impl MatchAgent {
         Self::new(MatchingStrategy::Fuzzy(FuzzyOptions::default()))
     }
 
+    /// Creates an agent with fuzzy matching limited by an absolute `max_distance` and by
+    /// `threshold_factor` times the input length (in characters). A candidate is only
+    /// accepted if its distance satisfies both limits; the stricter one wins.
+    pub fn new_fuzzy_with(max_distance: usize, threshold_factor: f32) -> Self {
+        Self::new(MatchingStrategy::Fuzzy(FuzzyOptions {
+            max_distance: Some(max_distance),
+            threshold_factor: Some(threshold_factor),
+            ..Default::default()
+        }))
+    }
+
+    /// Creates an agent with substring matching strategy
+    pub fn new_contains(bidirectional: bool) -> Self {
+        Self::new(MatchingStrategy::Contains { bidirectional })
+    }
+
+    /// Normalizes stored inputs and queries with `preprocessor` before matching.
+    /// Must be set before training.
+    pub fn with_input_preprocessor(mut self, preprocessor: InputPreprocessor) -> Self {
+        self.input_preprocessor = Some(preprocessor);
+        self
+    }
+
+    /// Sets the confidence an answer must exceed for `can_answer` (default: 0.5)
+    pub fn with_confidence_threshold(mut self, threshold: f32) -> Self {
+        self.confidence_threshold = threshold;
+        self
+    }
+
+    /// Controls whether the exact strategy collapses whitespace and maps curly quotes and
+    /// dashes to their ASCII forms on both sides before comparing (default: on).
+    /// Must be set before training.
+    pub fn with_normalization(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Truncates queries to `max_chars` characters before matching, so pasted walls of
+    /// text stay cheap to compare (default: unlimited). `predict_with_metadata` flags
+    /// truncated queries with `input_truncated`.
+    pub fn with_max_input_chars(mut self, max_chars: usize) -> Self {
+        self.max_input_chars = Some(max_chars);
+        self
+    }
+
+    /// Compares inputs in the exact strategy with full Unicode case folding instead of
+    /// `to_lowercase`, so e.g. "STRASSE" matches "straße" (default: off, only lowercasing).
+    /// Must be set before training.
+    pub fn with_case_folding(mut self, enabled: bool) -> Self {
+        self.case_folding = enabled;
+        self
+    }
+
+    /// Key used for exact lookups of an already prepared text
+    fn exact_key(&self, prepared: String) -> String {
+        let prepared = if self.case_folding {
+            caseless::default_case_fold_str(&prepared)
+        } else {
+            prepared
+        };
+
+        if self.normalize {
+            text_utils::normalize_punctuation(&prepared)
+        } else {
+            prepared
+        }
+    }
+
+    /// Applies the input preprocessor (if any) and lowercases the text
+    fn prepare(&self, text: &str) -> String {
+        match &self.input_preprocessor {
+            Some(preprocessor) => preprocessor(text).to_lowercase(),
+            None => text.to_lowercase(),
+        }
+    }
+
     /// Allows changing the matching strategy after agent creation
     pub fn with_strategy(mut self, strategy: MatchingStrategy) -> Self {
         self.strategy = strategy;
+        self.rebuild_exact_index();
         self
     }
-}
 
-impl Agent for MatchAgent {
-    /// Predicts the best matching response based on the current strategy
-    fn predict(&self, input: &str) -> ResponseFormat {
-        if self.memory.is_empty() {
-            return ResponseFormat::Text("No training data available.".to_string());
+    /// Indexes the stored keys for the exact strategy; other strategies keep no index.
+    /// Among equal inputs the highest weight wins, earlier examples win ties.
+    fn rebuild_exact_index(&mut self) {
+        self.exact_index.clear();
+        if !matches!(self.strategy, MatchingStrategy::Exact) {
+            self.exact_index.shrink_to_fit();
+            return;
+        }
+
+        for (idx, item) in self.memory.iter().enumerate() {
+            let key = self.exact_key(self.keys[idx].clone());
+            match self.exact_index.get(&key) {
+                Some(&best) if self.memory[best].weight >= item.weight => {}
+                _ => {
+                    self.exact_index.insert(key, idx);
+                }
+            }
         }
+    }
+}
 
+impl MatchAgent {
+    /// Returns the stored answer of the best match without cloning it, or None on a miss
+    pub fn predict_ref(&self, input: &str) -> Option<&ResponseFormat> {
+        self.find_match(input).map(|(item, _)| &item.output)
+    }
+
+    /// Finds the best matching example together with the confidence of the match
+    fn find_match(&self, input: &str) -> Option<(&TrainingExample, f32)> {
+        if input.trim().is_empty() {
+            return None;
+        }
+        let input = limit_input(input, self.max_input_chars);
         match &self.strategy {
             MatchingStrategy::Exact => {
-                // Exact match strategy
-                for item in &self.memory {
-                    if item.input.to_lowercase() == input.to_lowercase() {
-                        return item.output.clone();
-                    }
-                }
-                ResponseFormat::Text("No matching answer found.".to_string())
+                // Exact match strategy using the lookup table built during training
+                self.exact_index
+                    .get(&self.exact_key(self.prepare(input)))
+                    .map(|&idx| (&self.memory[idx], 1.0))
             }
             MatchingStrategy::Fuzzy(options) => {
-                // Fuzzy matching strategy using Levenshtein distance
+                // Fuzzy matching strategy using the configured edit distance
                 let mut best_score = usize::MAX;
                 let mut best_match = None;
 
-                let input_lower = input.to_lowercase();
+                let input_lower = self.prepare(input);
 
                 // Calculate dynamic threshold based on input length
                 let threshold = match options.threshold_factor {
-                    Some(factor) => (input_lower.len() as f32 * factor) as usize,
+                    Some(factor) => (input_lower.chars().count() as f32 * factor) as usize,
                     None => usize::MAX,
                 };
 
-                for item in &self.memory {
-                    let score = levenshtein(&item.input.to_lowercase(), &input_lower);
+                // Nothing beats an exact match, so the scan can always stop there
+                let good_enough = options.good_enough_distance.unwrap_or(0);
+
+                for (idx, key) in self.keys.iter().enumerate() {
+                    #[cfg(test)]
+                    KEY_COMPARISONS.with(|count| count.set(count.get() + 1));
+                    let score = options.algorithm.distance(key, &input_lower);
 
                     // Check max distance constraint
                     if let Some(max_dist) = options.max_distance {
@@ -121,22 +313,532 @@ impl Agent for MatchAgent {
                     // Find best match
                     if score < best_score {
                         best_score = score;
-                        best_match = Some(item);
+                        best_match = Some(idx);
+                    }
+
+                    if best_score <= good_enough {
+                        break;
                     }
                 }
 
-                match best_match {
-                    Some(item) => item.output.clone(),
-                    None => ResponseFormat::Text("No matching answer found.".to_string()),
+                // Confidence decreases with the distance relative to the longer string
+                best_match.map(|idx| {
+                    let distance = options.algorithm.normalized(&self.keys[idx], &input_lower);
+                    (&self.memory[idx], 1.0 - distance)
+                })
+            }
+            MatchingStrategy::Contains { bidirectional } => {
+                // Substring matching strategy
+                let input_lower = self.prepare(input).trim().to_string();
+                if input_lower.is_empty() {
+                    return None;
+                }
+
+                let mut best_match: Option<usize> = None;
+
+                for (idx, item_lower) in self.keys.iter().enumerate() {
+                    #[cfg(test)]
+                    KEY_COMPARISONS.with(|count| count.set(count.get() + 1));
+                    let matches = item_lower.contains(&input_lower)
+                        || (*bidirectional
+                            && !item_lower.is_empty()
+                            && input_lower.contains(item_lower.as_str()));
+
+                    if !matches {
+                        continue;
+                    }
+
+                    // Prefer the shortest stored input
+                    if best_match.is_none_or(|best| {
+                        item_lower.chars().count() < self.keys[best].chars().count()
+                    }) {
+                        best_match = Some(idx);
+                    }
                 }
+
+                // Confidence is the share of the longer string covered by the shorter one
+                best_match.map(|idx| {
+                    let query_len = input_lower.chars().count() as f32;
+                    let item_len = self.keys[idx].chars().count() as f32;
+                    (
+                        &self.memory[idx],
+                        query_len.min(item_len) / query_len.max(item_len),
+                    )
+                })
             }
         }
     }
 }
 
+impl Agent for MatchAgent {
+    /// Predicts the best matching response based on the current strategy
+    fn predict(&self, input: &str) -> ResponseFormat {
+        if self.memory.is_empty() {
+            return ResponseFormat::Text("No training data available.".to_string());
+        }
+
+        if input.trim().is_empty() {
+            return ResponseFormat::Text(EMPTY_QUERY_RESPONSE.to_string());
+        }
+
+        match self.predict_ref(input) {
+            Some(output) => output.clone(),
+            None => ResponseFormat::Text("No matching answer found.".to_string()),
+        }
+    }
+
+    /// Predicts like `predict`, flagging queries shortened by `with_max_input_chars`
+    fn predict_with_metadata(&self, input: &str) -> PredictionResult {
+        PredictionResult {
+            response: self.predict(input),
+            confidence: self.confidence(input),
+            metadata: truncation_metadata(input, self.max_input_chars),
+        }
+    }
+
+    /// Reports how closely the best matching example fits the input
+    fn confidence(&self, input: &str) -> f32 {
+        self.find_match(input)
+            .map_or(0.0, |(_, confidence)| confidence)
+    }
+
+    /// Returns the configured threshold for `can_answer`
+    fn confidence_threshold(&self) -> f32 {
+        self.confidence_threshold
+    }
+
+    /// Returns the inputs of the stored examples in training order
+    fn known_inputs(&self) -> Vec<&str> {
+        self.memory.iter().map(|item| item.input.as_str()).collect()
+    }
+
+    /// Returns "Text" plus the formats of the stored answers
+    fn supported_formats(&self) -> Vec<&'static str> {
+        supported_formats_of(&self.memory)
+    }
+}
+
 impl TrainableAgent for MatchAgent {
     /// Trains the agent by storing training examples
     fn train(&mut self, data: &[TrainingExample]) {
         self.memory = data.to_vec();
+        self.keys = self
+            .memory
+            .iter()
+            .map(|item| self.prepare(&item.input))
+            .collect();
+        self.rebuild_exact_index();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::INPUT_TRUNCATED_KEY;
+
+    fn example(input: &str, output: &str, weight: f32) -> TrainingExample {
+        TrainingExample {
+            input: input.to_string(),
+            output: ResponseFormat::Text(output.to_string()),
+            weight,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_exact_normalization() {
+        let data = [
+            example("Hello world", "greeting", 1.0),
+            example("What\u{2019}s \u{201C}airust\u{201D}?", "library", 1.0),
+        ];
+        let mut agent = MatchAgent::new_exact();
+        agent.train(&data);
+
+        assert_eq!(agent.predict_text("  Hello   world "), "greeting");
+        assert_eq!(agent.predict_text("What's \"airust\"?"), "library");
+
+        let mut strict = MatchAgent::new_exact().with_normalization(false);
+        strict.train(&data);
+        assert_eq!(strict.confidence("  Hello   world "), 0.0);
+        assert_eq!(strict.confidence("What's \"airust\"?"), 0.0);
+        assert_eq!(strict.predict_text("Hello world"), "greeting");
+    }
+
+    #[test]
+    fn test_empty_query() {
+        let data = [example("", "blank", 1.0), example("hello", "world", 1.0)];
+        let agents = [
+            MatchAgent::new_exact(),
+            MatchAgent::new(MatchingStrategy::Fuzzy(FuzzyOptions {
+                threshold_factor: None,
+                ..Default::default()
+            })),
+            MatchAgent::new_contains(true),
+        ];
+
+        for mut agent in agents {
+            agent.train(&data);
+            for query in ["", "   "] {
+                assert_eq!(agent.predict_text(query), EMPTY_QUERY_RESPONSE);
+                assert_eq!(agent.confidence(query), 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_exact_case_folding() {
+        let data = [example("Hauptstraße", "street", 1.0)];
+
+        // Lowercasing keeps "ß" and "ss" apart
+        let mut agent = MatchAgent::new_exact();
+        agent.train(&data);
+        assert_eq!(agent.confidence("HAUPTSTRASSE"), 0.0);
+        assert_eq!(agent.predict_text("HAUPTSTRAßE"), "street");
+
+        let mut agent = MatchAgent::new_exact().with_case_folding(true);
+        agent.train(&data);
+        assert_eq!(agent.predict_text("HAUPTSTRASSE"), "street");
+        assert_eq!(agent.predict_text("hauptstrasse"), "street");
+    }
+
+    #[test]
+    fn test_max_input_chars_truncates_queries() {
+        let mut agent = MatchAgent::new_exact().with_max_input_chars(5);
+        agent.train(&[example("hello", "world", 1.0)]);
+
+        let result = agent.predict_with_metadata("hello there, this is a very long paste");
+        assert_eq!(String::from(result.response), "world");
+        assert_eq!(result.metadata.unwrap()[INPUT_TRUNCATED_KEY], true);
+
+        let result = agent.predict_with_metadata("hello");
+        assert_eq!(String::from(result.response), "world");
+        assert!(result.metadata.is_none());
+    }
+
+    #[test]
+    fn test_supported_formats_follow_training_data() {
+        let mut agent = MatchAgent::new_exact();
+        assert_eq!(agent.supported_formats(), vec!["Text"]);
+
+        agent.train(&[example("hello", "world", 1.0)]);
+        assert_eq!(agent.supported_formats(), vec!["Text"]);
+
+        agent.train(&[
+            example("hello", "world", 1.0),
+            TrainingExample {
+                input: "status".to_string(),
+                output: ResponseFormat::Json(serde_json::json!({"ok": true})),
+                weight: 1.0,
+                metadata: None,
+            },
+        ]);
+        assert_eq!(agent.supported_formats(), vec!["Text", "Json"]);
+    }
+
+    #[test]
+    fn test_damerau_levenshtein_accepts_transpositions() {
+        let data = [example("teh manual", "See the manual", 1.0)];
+        let strategy = |algorithm| {
+            MatchingStrategy::Fuzzy(FuzzyOptions {
+                max_distance: Some(1),
+                threshold_factor: None,
+                algorithm,
+                good_enough_distance: None,
+            })
+        };
+
+        // The swapped "he" costs two edits with Levenshtein
+        let mut agent = MatchAgent::new(strategy(DistanceAlgorithm::Levenshtein));
+        agent.train(&data);
+        assert_eq!(agent.confidence("the manual"), 0.0);
+
+        let mut agent = MatchAgent::new(strategy(DistanceAlgorithm::DamerauLevenshtein));
+        agent.train(&data);
+        assert_eq!(String::from(agent.predict("the manual")), "See the manual");
+        assert!((agent.confidence("the manual") - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_predict_ref_borrows_stored_answer() {
+        let mut agent = MatchAgent::new_fuzzy();
+        agent.train(&[example("What is airust?", "A Rust library", 1.0)]);
+
+        let answer = agent.predict_ref("what is airust").unwrap();
+        assert_eq!(
+            answer.to_string(),
+            agent.predict("what is airust").to_string()
+        );
+        assert!(std::ptr::eq(answer, &agent.memory[0].output));
+        assert!(agent.predict_ref("something else entirely").is_none());
+    }
+
+    #[test]
+    fn test_known_inputs() {
+        let data = [
+            example("What is airust?", "A Rust library", 1.0),
+            example("What is BM25?", "A ranking function", 1.0),
+        ];
+        let mut agent = MatchAgent::new_exact();
+        assert!(agent.known_inputs().is_empty());
+        agent.train(&data);
+        assert_eq!(
+            agent.known_inputs(),
+            vec!["What is airust?", "What is BM25?"]
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_scan_stops_early() {
+        let mut data: Vec<TrainingExample> = (0..50)
+            .map(|i| example(&format!("question number {}", i), "filler", 1.0))
+            .collect();
+        data.insert(10, example("reset password", "Use the reset link", 1.0));
+        data.push(example("reset pasword", "Typo answer", 1.0));
+
+        let comparisons = |agent: &MatchAgent, query: &str| {
+            KEY_COMPARISONS.with(|count| count.set(0));
+            let answer = String::from(agent.predict(query));
+            (answer, KEY_COMPARISONS.with(Cell::get))
+        };
+
+        let mut agent = MatchAgent::new_fuzzy();
+        agent.train(&data);
+
+        // An exact match ends the scan without changing the answer
+        let (answer, count) = comparisons(&agent, "reset password");
+        assert_eq!(answer, "Use the reset link");
+        assert_eq!(count, 11);
+
+        // Without an exact match the full scan runs and finds the closest example
+        let (answer, count) = comparisons(&agent, "reset passwrd");
+        assert_eq!(answer, "Use the reset link");
+        assert_eq!(count, data.len());
+
+        // A good-enough distance accepts the first close candidate, even though the
+        // exact match "reset pasword" comes later
+        let mut agent = MatchAgent::new(MatchingStrategy::Fuzzy(FuzzyOptions {
+            good_enough_distance: Some(2),
+            ..Default::default()
+        }));
+        agent.train(&data);
+        let (answer, count) = comparisons(&agent, "reset pasword");
+        assert_eq!(answer, "Use the reset link");
+        assert_eq!(count, 11);
+    }
+
+    #[test]
+    fn test_input_preprocessor_strips_markdown() {
+        let data = [
+            example("reset password", "Use the reset link", 1.0),
+            example("**reset** password now", "Contact support", 1.0),
+        ];
+        let strip_bold = || -> InputPreprocessor { Box::new(|text: &str| text.replace("**", "")) };
+
+        let mut plain = MatchAgent::new_exact();
+        plain.train(&data);
+        assert_eq!(
+            String::from(plain.predict("**reset** password")),
+            "No matching answer found."
+        );
+
+        let mut agent = MatchAgent::new_exact().with_input_preprocessor(strip_bold());
+        agent.train(&data);
+        assert_eq!(
+            String::from(agent.predict("**reset** password")),
+            "Use the reset link"
+        );
+        // Stored inputs are preprocessed too
+        assert_eq!(
+            String::from(agent.predict("reset password now")),
+            "Contact support"
+        );
+
+        let mut fuzzy = MatchAgent::new_fuzzy().with_input_preprocessor(strip_bold());
+        fuzzy.train(&data);
+        assert_eq!(fuzzy.confidence("**reset password**"), 1.0);
+    }
+
+    #[test]
+    fn test_fuzzy_with_requires_both_limits() {
+        let data = [example("hello world", "greeting", 1.0)];
+        // "helo wrld" has distance 2 and 9 characters
+        let matches = |max_distance, threshold_factor| {
+            let mut agent = MatchAgent::new_fuzzy_with(max_distance, threshold_factor);
+            agent.train(&data);
+            agent.confidence("helo wrld") > 0.0
+        };
+
+        // Within both limits
+        assert!(matches(2, 0.3));
+        // Passes the relative threshold (4) but exceeds the absolute maximum
+        assert!(!matches(1, 0.5));
+        // Passes the absolute maximum but exceeds the relative threshold (0)
+        assert!(!matches(5, 0.1));
+    }
+
+    #[test]
+    fn test_threshold_factor_counts_characters() {
+        let data = [example("ubergrosse", "size", 1.0)];
+        let mut agent = MatchAgent::new(MatchingStrategy::Fuzzy(FuzzyOptions {
+            max_distance: None,
+            threshold_factor: Some(0.4),
+            ..Default::default()
+        }));
+        agent.train(&data);
+
+        // "übergröße" is 9 characters but 12 bytes and has distance 4: the threshold is
+        // 9 * 0.4 = 3, whereas the byte length would have allowed 4
+        assert_eq!(agent.confidence("übergröße"), 0.0);
+        // "übergröse" (9 characters, distance 3) stays within the threshold
+        assert!(agent.confidence("übergröse") > 0.0);
+    }
+
+    #[test]
+    fn test_exact_prefers_highest_weight() {
+        let mut agent = MatchAgent::new_exact();
+        agent.train(&[
+            example("What is Rust?", "A game", 0.5),
+            example("what is rust?", "A systems programming language", 2.0),
+            example("What is Rust?", "An iron oxide", 1.0),
+        ]);
+
+        assert_eq!(
+            agent.predict_text("What is Rust?"),
+            "A systems programming language"
+        );
+    }
+
+    #[test]
+    fn test_exact_lookup_on_large_memory() {
+        let data: Vec<TrainingExample> = (0..50_000)
+            .map(|i| example(&format!("Question {}", i), &format!("Answer {}", i), 1.0))
+            .collect();
+
+        let mut agent = MatchAgent::new_exact();
+        agent.train(&data);
+        assert_eq!(agent.exact_index.len(), data.len());
+
+        // Lookups go through the index without comparing any stored key
+        KEY_COMPARISONS.with(|count| count.set(0));
+        for i in (0..50_000).step_by(25) {
+            assert_eq!(
+                agent.predict_text(&format!("question {}", i)),
+                format!("Answer {}", i)
+            );
+        }
+        assert_eq!(
+            agent.predict_text("Question 50000"),
+            "No matching answer found."
+        );
+        assert_eq!(KEY_COMPARISONS.with(Cell::get), 0);
+
+        // A scanning strategy compares every stored key and keeps no index
+        let agent = agent.with_strategy(MatchingStrategy::Contains {
+            bidirectional: false,
+        });
+        assert!(agent.exact_index.is_empty());
+        agent.predict_text("question 49999");
+        assert_eq!(KEY_COMPARISONS.with(Cell::get), data.len());
+
+        // Switching back to exact matching builds the index again
+        let agent = agent.with_strategy(MatchingStrategy::Exact);
+        assert_eq!(agent.exact_index.len(), data.len());
+        assert_eq!(agent.predict_text("question 7"), "Answer 7");
+    }
+
+    #[test]
+    fn test_exact_index_only_for_exact_strategy() {
+        let data = [example("hello", "world", 1.0)];
+        let mut fuzzy = MatchAgent::new_fuzzy();
+        fuzzy.train(&data);
+        assert!(fuzzy.exact_index.is_empty());
+
+        let mut contains = MatchAgent::new_contains(true);
+        contains.train(&data);
+        assert!(contains.exact_index.is_empty());
+        assert_eq!(contains.predict_text("hello"), "world");
+    }
+
+    #[test]
+    fn test_exact_ties_prefer_earliest() {
+        let mut agent = MatchAgent::new_exact();
+        agent.train(&[
+            example("hello", "first", 1.0),
+            example("HELLO", "second", 1.0),
+        ]);
+        assert_eq!(agent.predict_text("Hello"), "first");
+    }
+
+    #[test]
+    fn test_confidence_reflects_match_quality() {
+        let data = [example("What is airust?", "A Rust AI library", 1.0)];
+
+        let mut exact = MatchAgent::new_exact();
+        exact.train(&data);
+        assert_eq!(exact.confidence("what is airust?"), 1.0);
+        assert_eq!(exact.confidence("What is Python?"), 0.0);
+
+        let mut fuzzy = MatchAgent::new_fuzzy();
+        fuzzy.train(&data);
+        assert_eq!(fuzzy.confidence("What is airust?"), 1.0);
+
+        // A typo still matches, but not with full confidence
+        let typo = fuzzy.confidence("What is Ayrast?");
+        assert_eq!(fuzzy.predict_text("What is Ayrast?"), "A Rust AI library");
+        assert!(typo < 1.0 && typo > 0.5);
+
+        let mut contains = MatchAgent::new_contains(false);
+        contains.train(&data);
+        assert!(contains.confidence("air") < 0.5);
+        assert!(!contains.can_answer("air"));
+    }
+
+    #[test]
+    fn test_confidence_threshold_controls_can_answer() {
+        let data = [example("What is airust?", "A Rust AI library", 1.0)];
+        let borderline = "What is Ayrast?";
+
+        let mut lenient = MatchAgent::new_fuzzy();
+        lenient.train(&data);
+        assert!(lenient.can_answer(borderline));
+
+        let mut strict = MatchAgent::new_fuzzy().with_confidence_threshold(0.9);
+        strict.train(&data);
+        assert_eq!(
+            strict.confidence(borderline),
+            lenient.confidence(borderline)
+        );
+        assert!(!strict.can_answer(borderline));
+        assert!(strict.can_answer("What is airust?"));
+    }
+
+    #[test]
+    fn test_contains_matches_substring() {
+        let mut agent = MatchAgent::new_contains(false);
+        agent.train(&[
+            example("Password reset via email", "Check your inbox", 1.0),
+            example("Password reset", "Use the reset link", 1.0),
+            example("Account deletion", "Contact support", 1.0),
+        ]);
+
+        assert_eq!(agent.predict_text("pass"), "Use the reset link");
+        assert_eq!(agent.predict_text("DELETION"), "Contact support");
+        assert_eq!(
+            agent.predict_text("password reset steps"),
+            "No matching answer found."
+        );
+    }
+
+    #[test]
+    fn test_contains_bidirectional() {
+        let mut agent = MatchAgent::new_contains(true);
+        agent.train(&[example("password reset", "Use the reset link", 1.0)]);
+
+        assert_eq!(
+            agent.predict_text("password reset steps"),
+            "Use the reset link"
+        );
+        assert_eq!(agent.predict_text("pass"), "Use the reset link");
+        assert_eq!(agent.predict_text("billing"), "No matching answer found.");
     }
 }
@@ -1,6 +1,8 @@
 // src/pdf_loader.rs - PDF to Training Data Converter
-use crate::agent::{ResponseFormat, TrainingExample};
+use crate::agent::{text_utils, ResponseFormat, TrainingExample};
 use crate::knowledge::KnowledgeBase;
+use once_cell::sync::Lazy;
+use regex::Regex;
 use std::error::Error;
 use std::fmt;
 
@@ -12,6 +14,7 @@ pub enum PdfError {
     IoError(std::io::Error),
     PdfError(String),
     InvalidPath(String),
+    InvalidConfig(String),
 }
 
 impl fmt::Display for PdfError {
@@ -20,6 +23,7 @@ impl fmt::Display for PdfError {
             PdfError::IoError(err) => write!(f, "IO Error: {}", err),
             PdfError::PdfError(msg) => write!(f, "PDF Error: {}", msg),
             PdfError::InvalidPath(path) => write!(f, "Invalid Path: {}", path),
+            PdfError::InvalidConfig(msg) => write!(f, "Invalid Configuration: {}", msg),
         }
     }
 }
@@ -43,6 +47,9 @@ pub struct PdfLoaderConfig {
     /// Overlap between chunks (in characters)
     pub chunk_overlap: usize,
 
+    /// Overlap as a fraction of `max_chunk_size` in `[0, 1)`; overrides `chunk_overlap` when set
+    pub chunk_overlap_ratio: Option<f32>,
+
     /// Default weight for generated training examples
     pub default_weight: f32,
 
@@ -51,21 +58,88 @@ pub struct PdfLoaderConfig {
 
     /// Whether chunks should be split at sentence boundaries
     pub split_by_sentence: bool,
+
+    /// Characters that end a sentence
+    pub sentence_terminators: Vec<char>,
+
+    /// How text is divided into the sentences that chunks are built from
+    pub sentence_segmenter: SentenceSegmenter,
+
+    /// Whether a new chunk starts at every heading line (recorded as `heading` in metadata)
+    pub split_by_heading: bool,
 }
 
+/// Per-file outcome of a batch conversion: the number of examples or the error
+pub type BatchReport = Vec<(PathBuf, Result<usize, PdfError>)>;
+
+/// Strategies for splitting text into sentence-like units before chunking
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SentenceSegmenter {
+    /// Split after each of the configured `sentence_terminators`
+    #[default]
+    Punctuation,
+    /// Split after each line break, for text without reliable punctuation
+    Lines,
+    /// Split into windows of this many characters (at least one)
+    FixedWindow(usize),
+}
+
+/// Numbered headings such as "2 Setup" or "1.2.3 Title"
+static NUMBERED_HEADING: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\d+(\.\d+)*\.?\s+\p{L}").unwrap());
+
+/// Maximum length (in characters) of a line that is considered a heading
+const MAX_HEADING_CHARS: usize = 80;
+
+/// Default sentence terminators covering Latin, CJK, Arabic and Devanagari scripts
+pub const DEFAULT_SENTENCE_TERMINATORS: &[char] = &['.', '!', '?', '。', '！', '？', '؟', '।', '…'];
+
 impl Default for PdfLoaderConfig {
     fn default() -> Self {
         Self {
-            min_chunk_size: 50,      // At least 50 characters per chunk
-            max_chunk_size: 1000,    // Maximum 1000 characters per chunk
-            chunk_overlap: 200,      // 200 characters overlap
-            default_weight: 1.0,     // Default weight for all chunks
-            include_metadata: true,  // Include metadata by default
-            split_by_sentence: true, // Split at sentence boundaries
+            min_chunk_size: 50,        // At least 50 characters per chunk
+            max_chunk_size: 1000,      // Maximum 1000 characters per chunk
+            chunk_overlap: 200,        // 200 characters overlap
+            chunk_overlap_ratio: None, // Use the absolute overlap
+            default_weight: 1.0,       // Default weight for all chunks
+            include_metadata: true,    // Include metadata by default
+            split_by_sentence: true,   // Split at sentence boundaries
+            sentence_terminators: DEFAULT_SENTENCE_TERMINATORS.to_vec(),
+            sentence_segmenter: SentenceSegmenter::Punctuation,
+            split_by_heading: false, // Ignore headings by default
         }
     }
 }
 
+impl PdfLoaderConfig {
+    /// Returns the overlap in characters, derived from `chunk_overlap_ratio` if set.
+    /// Fails if the ratio is outside `[0, 1)` or the overlap is not smaller than
+    /// `max_chunk_size`.
+    pub fn effective_chunk_overlap(&self) -> Result<usize, PdfError> {
+        let overlap = match self.chunk_overlap_ratio {
+            None => self.chunk_overlap,
+            Some(ratio) if (0.0..1.0).contains(&ratio) => {
+                (self.max_chunk_size as f32 * ratio) as usize
+            }
+            Some(ratio) => {
+                return Err(PdfError::InvalidConfig(format!(
+                    "chunk_overlap_ratio must be in [0, 1), got {}",
+                    ratio
+                )))
+            }
+        };
+
+        if overlap > 0 && overlap >= self.max_chunk_size {
+            return Err(PdfError::InvalidConfig(format!(
+                "chunk_overlap ({}) must be smaller than max_chunk_size ({})",
+                overlap, self.max_chunk_size
+            )));
+        }
+
+        Ok(overlap)
+    }
+}
+
 pub struct PdfLoader {
     config: PdfLoaderConfig,
 }
@@ -90,12 +164,7 @@ impl PdfLoader {
     ) -> Result<KnowledgeBase, PdfError> {
         let examples = self.pdf_to_training_examples(path)?;
 
-        let mut kb = KnowledgeBase::new();
-        for example in examples {
-            kb.add_example(example.input, example.output, example.weight);
-        }
-
-        Ok(kb)
+        Ok(KnowledgeBase::from_examples(examples))
     }
 
     /// Loads a PDF and converts it to TrainingExamples
@@ -104,6 +173,7 @@ impl PdfLoader {
         path: P,
     ) -> Result<Vec<TrainingExample>, PdfError> {
         let path = path.as_ref();
+        self.config.effective_chunk_overlap()?;
         if !path.exists() {
             return Err(PdfError::InvalidPath(path.to_string_lossy().to_string()));
         }
@@ -117,6 +187,63 @@ impl PdfLoader {
         Ok(examples)
     }
 
+    /// Converts every PDF in `dir` (not recursive) and merges the examples into one
+    /// knowledge base. Files are processed in filename order and merged in that order, so
+    /// the result is stable; with the `parallel` feature they are converted concurrently.
+    /// A file that fails is reported in the summary without aborting the others.
+    pub fn convert_directory<P: AsRef<Path>>(
+        &self,
+        dir: P,
+    ) -> Result<(KnowledgeBase, BatchReport), PdfError> {
+        self.config.effective_chunk_overlap()?;
+
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir.as_ref())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+            })
+            .collect();
+        files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+        let mut examples = Vec::new();
+        let mut report = Vec::with_capacity(files.len());
+        for (path, result) in files.iter().zip(self.convert_files(&files)) {
+            report.push((
+                path.clone(),
+                result.map(|file_examples| {
+                    let count = file_examples.len();
+                    examples.extend(file_examples);
+                    count
+                }),
+            ));
+        }
+
+        Ok((KnowledgeBase::from_examples(examples), report))
+    }
+
+    /// Converts the files, keeping the results in the order of `files`
+    #[cfg(feature = "parallel")]
+    fn convert_files(&self, files: &[PathBuf]) -> Vec<Result<Vec<TrainingExample>, PdfError>> {
+        use rayon::prelude::*;
+
+        files
+            .par_iter()
+            .map(|path| self.pdf_to_training_examples(path))
+            .collect()
+    }
+
+    /// Converts the files, keeping the results in the order of `files`
+    #[cfg(not(feature = "parallel"))]
+    fn convert_files(&self, files: &[PathBuf]) -> Vec<Result<Vec<TrainingExample>, PdfError>> {
+        files
+            .iter()
+            .map(|path| self.pdf_to_training_examples(path))
+            .collect()
+    }
+
     /// Extracts text from a PDF file
     fn extract_text_from_pdf(&self, path: &Path) -> Result<String, PdfError> {
         // Use pdf-extract to extract text
@@ -129,15 +256,36 @@ impl PdfLoader {
     /// Splits text into chunks and creates TrainingExamples
     fn text_to_training_examples(&self, text: &str) -> Vec<TrainingExample> {
         let mut examples = Vec::new();
-        let chunks = self.split_text_into_chunks(text);
 
-        for (i, chunk) in chunks.iter().enumerate() {
+        // Chunk each section separately so chunks never cross a heading
+        let chunks: Vec<(Option<String>, String)> = if self.config.split_by_heading {
+            self.split_into_sections(text)
+                .into_iter()
+                .flat_map(|(heading, body)| {
+                    self.split_text_into_chunks(&body)
+                        .into_iter()
+                        .map(move |chunk| (heading.clone(), chunk))
+                })
+                .collect()
+        } else {
+            self.split_text_into_chunks(text)
+                .into_iter()
+                .map(|chunk| (None, chunk))
+                .collect()
+        };
+
+        for (i, (heading, chunk)) in chunks.iter().enumerate() {
             // Create metadata if configured
             let metadata = if self.config.include_metadata {
-                Some(serde_json::json!({
+                let mut metadata = serde_json::json!({
                     "chunk_index": i,
                     "total_chunks": chunks.len(),
-                }))
+                    "chunk_id": Self::chunk_id(chunk),
+                });
+                if let Some(heading) = heading {
+                    metadata["heading"] = serde_json::json!(heading);
+                }
+                Some(metadata)
             } else {
                 None
             };
@@ -154,7 +302,63 @@ impl PdfLoader {
         examples
     }
 
-    /// Splits text into overlapping chunks while respecting UTF-8 characters
+    /// Stable identifier of a chunk derived from its text only
+    pub fn chunk_id(chunk: &str) -> String {
+        format!("{:016x}", text_utils::stable_hash(chunk))
+    }
+
+    /// Checks whether a line looks like a heading: all caps, numbered ("1.2 Title"),
+    /// or a short line standing alone between blank lines without sentence punctuation
+    fn is_heading(&self, line: &str, standalone: bool) -> bool {
+        let line = line.trim();
+        if line.is_empty() || line.chars().count() > MAX_HEADING_CHARS {
+            return false;
+        }
+
+        let has_letters = line.chars().any(|c| c.is_alphabetic());
+        let all_caps = has_letters && !line.chars().any(|c| c.is_lowercase());
+        let ends_sentence = line
+            .chars()
+            .last()
+            .is_some_and(|c| self.config.sentence_terminators.contains(&c));
+
+        all_caps || NUMBERED_HEADING.is_match(line) || (standalone && has_letters && !ends_sentence)
+    }
+
+    /// Splits text into sections starting at heading lines.
+    /// Text before the first heading forms a section without heading.
+    fn split_into_sections(&self, text: &str) -> Vec<(Option<String>, String)> {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut sections = Vec::new();
+        let mut heading: Option<String> = None;
+        let mut body = String::new();
+
+        for (i, line) in lines.iter().enumerate() {
+            let prev_blank = i == 0 || lines[i - 1].trim().is_empty();
+            let next_blank = i + 1 == lines.len() || lines[i + 1].trim().is_empty();
+
+            if self.is_heading(line, prev_blank && next_blank) {
+                if !body.trim().is_empty() {
+                    sections.push((heading.take(), body.clone()));
+                }
+                heading = Some(line.trim().to_string());
+                body.clear();
+            }
+
+            body.push_str(line);
+            body.push('\n');
+        }
+
+        if !body.trim().is_empty() {
+            sections.push((heading, body));
+        }
+
+        sections
+    }
+
+    /// Splits text into overlapping chunks while respecting UTF-8 characters.
+    /// Sentences within a chunk keep the whitespace between them from the source text,
+    /// but a chunk never starts with the whitespace that separated it from the previous one.
     fn split_text_into_chunks(&self, text: &str) -> Vec<String> {
         let mut chunks = Vec::new();
         let text = text.trim();
@@ -171,12 +375,20 @@ impl PdfLoader {
 
         // Split text into sentences if configured
         let segments = if self.config.split_by_sentence {
-            self.split_into_sentences(text)
+            self.sentence_segments(text)
         } else {
             // Otherwise split into characters - but correctly as UTF-8
             text.chars().map(|c| c.to_string()).collect()
         };
 
+        // Invalid overlaps are rejected by pdf_to_training_examples; for plain text the
+        // absolute overlap is clamped below max_chunk_size so the arithmetic can't underflow
+        let chunk_overlap = self.config.effective_chunk_overlap().unwrap_or_else(|_| {
+            self.config
+                .chunk_overlap
+                .min(self.config.max_chunk_size.saturating_sub(1))
+        });
+
         let mut current_chunk = String::new();
 
         for segment in segments {
@@ -188,11 +400,11 @@ impl PdfLoader {
                     chunks.push(current_chunk.clone());
 
                     // Start new chunk with overlap
-                    if self.config.chunk_overlap > 0 {
+                    if chunk_overlap > 0 {
                         // Keep the last chunk_overlap characters (UTF-8 safe)
-                        if current_chunk.chars().count() > self.config.chunk_overlap {
+                        if current_chunk.chars().count() > chunk_overlap {
                             let chars: Vec<char> = current_chunk.chars().collect();
-                            let overlap_start = chars.len() - self.config.chunk_overlap;
+                            let overlap_start = chars.len() - chunk_overlap;
                             current_chunk = chars[overlap_start..].iter().collect();
                         }
                     } else {
@@ -201,19 +413,26 @@ impl PdfLoader {
                 }
             }
 
-            // Add segment to current chunk
-            current_chunk.push_str(&segment);
+            // Add segment to current chunk, without leading whitespace at its start
+            if current_chunk.is_empty() {
+                current_chunk.push_str(segment.trim_start());
+            } else {
+                current_chunk.push_str(&segment);
+            }
 
             // If the chunk is now larger than max_chunk_size, split it
             let chunk_char_count = current_chunk.chars().count();
             if chunk_char_count > self.config.max_chunk_size {
                 // Safe splitting considering UTF-8 characters
                 let chars: Vec<char> = current_chunk.chars().collect();
-                chunks.push(chars[..self.config.max_chunk_size].iter().collect());
+                chunks.push(
+                    text_utils::truncate_chars(&current_chunk, self.config.max_chunk_size)
+                        .to_string(),
+                );
 
                 // Keep remainder with overlap
-                if self.config.chunk_overlap > 0 {
-                    let overlap_start = self.config.max_chunk_size - self.config.chunk_overlap;
+                if chunk_overlap > 0 {
+                    let overlap_start = self.config.max_chunk_size - chunk_overlap;
                     current_chunk = chars[overlap_start..].iter().collect();
                 } else {
                     current_chunk = chars[self.config.max_chunk_size..].iter().collect();
@@ -230,9 +449,34 @@ impl PdfLoader {
         chunks
     }
 
-    /// Splits text into sentences
-    fn split_into_sentences(&self, text: &str) -> Vec<String> {
-        // Simple sentence splitting based on periods, question marks, and exclamation marks
+    /// Splits text into sentences without the whitespace around them; whitespace-only
+    /// segments (e.g. blank lines between sentences) are dropped
+    pub fn split_into_sentences(&self, text: &str) -> Vec<String> {
+        self.sentence_segments(text)
+            .into_iter()
+            .map(|sentence| sentence.trim().to_string())
+            .filter(|sentence| !sentence.is_empty())
+            .collect()
+    }
+
+    /// Splits text with the configured segmenter, keeping the whitespace between
+    /// sentences so the segments concatenate back to the original text
+    fn sentence_segments(&self, text: &str) -> Vec<String> {
+        match self.config.sentence_segmenter {
+            SentenceSegmenter::Punctuation => {}
+            SentenceSegmenter::Lines => {
+                return text.split_inclusive('\n').map(String::from).collect();
+            }
+            SentenceSegmenter::FixedWindow(size) => {
+                let chars: Vec<char> = text.chars().collect();
+                return chars
+                    .chunks(size.max(1))
+                    .map(|window| window.iter().collect())
+                    .collect();
+            }
+        }
+
+        // Simple sentence splitting based on the configured terminators
         // Can be replaced with a more complex NLP solution
         let mut sentences = Vec::new();
         let mut current_sentence = String::new();
@@ -241,7 +485,7 @@ impl PdfLoader {
             current_sentence.push(c);
 
             // Check if the end of a sentence has been reached
-            if ['.', '!', '?'].contains(&c) {
+            if self.config.sentence_terminators.contains(&c) {
                 sentences.push(current_sentence.clone());
                 current_sentence.clear();
             }
@@ -262,7 +506,13 @@ impl PdfLoader {
         path: P,
     ) -> Result<(), PdfError> {
         kb.save(Some(PathBuf::from(path.as_ref())))
-            .map_err(|e| PdfError::IoError(std::io::Error::new(std::io::ErrorKind::Other, e)))
+            .map_err(|e| PdfError::IoError(std::io::Error::other(e)))
+    }
+}
+
+impl Default for PdfLoader {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -290,6 +540,278 @@ mod tests {
         assert_eq!(sentences[2], "Is this a third sentence?");
     }
 
+    #[test]
+    fn test_sentence_whitespace_handling() {
+        let loader = PdfLoader::new();
+        assert_eq!(
+            loader.split_into_sentences("  First one.   Second one!\n\n Third"),
+            vec!["First one.", "Second one!", "Third"]
+        );
+        assert!(loader.split_into_sentences(" \n\t ").is_empty());
+
+        let text = "First sentence here. Second sentence here. Third one.";
+        let chunks = |max_chunk_size| {
+            PdfLoader::with_config(PdfLoaderConfig {
+                min_chunk_size: 1,
+                max_chunk_size,
+                chunk_overlap: 0,
+                ..Default::default()
+            })
+            .split_text_into_chunks(text)
+        };
+
+        // Sentences sharing a chunk keep the single space between them
+        assert_eq!(
+            chunks(45),
+            vec!["First sentence here. Second sentence here.", "Third one."]
+        );
+        // A new chunk does not start with the space before its first sentence
+        assert_eq!(
+            chunks(30),
+            vec![
+                "First sentence here.",
+                "Second sentence here.",
+                "Third one."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_into_sentences_non_latin() {
+        let loader = PdfLoader::new();
+        let sentences = loader.split_into_sentences("今天天气很好。我们去公园吧！你来吗？");
+        assert_eq!(
+            sentences,
+            vec!["今天天气很好。", "我们去公园吧！", "你来吗？"]
+        );
+
+        let sentences = loader.split_into_sentences("كيف حالك؟ أنا بخير.");
+        assert_eq!(sentences.len(), 2);
+
+        let config = PdfLoaderConfig {
+            sentence_terminators: vec![';'],
+            ..Default::default()
+        };
+        let loader = PdfLoader::with_config(config);
+        assert_eq!(loader.split_into_sentences("a; b. c; d").len(), 3);
+    }
+
+    #[test]
+    fn test_chunk_id_is_content_based() {
+        assert_eq!(
+            PdfLoader::chunk_id("Same text."),
+            PdfLoader::chunk_id("Same text.")
+        );
+        assert_ne!(
+            PdfLoader::chunk_id("Same text."),
+            PdfLoader::chunk_id("Other text.")
+        );
+
+        // The id does not depend on the chunk position or configuration
+        let text = "First sentence of the document. Second sentence of the document.";
+        let small = PdfLoader::with_config(PdfLoaderConfig {
+            min_chunk_size: 10,
+            max_chunk_size: 40,
+            chunk_overlap: 0,
+            ..Default::default()
+        });
+        let examples = small.text_to_training_examples(&format!("Preamble. {}", text));
+        let last = examples.last().unwrap();
+        assert_eq!(
+            last.metadata.as_ref().unwrap()["chunk_id"],
+            PdfLoader::chunk_id(&last.input)
+        );
+    }
+
+    #[test]
+    fn test_convert_directory_is_ordered_and_isolates_errors() {
+        let dir = std::env::temp_dir().join(format!("airust_pdf_batch_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("data/pdf/sample.pdf");
+        for name in ["c.pdf", "a.PDF"] {
+            std::fs::copy(&sample, dir.join(name)).unwrap();
+        }
+        std::fs::write(dir.join("b.pdf"), "this is not a pdf").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let loader = PdfLoader::new();
+        let single = loader.pdf_to_training_examples(&sample).unwrap();
+        let (kb, report) = loader.convert_directory(&dir).unwrap();
+
+        let names: Vec<String> = report
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["a.PDF", "b.pdf", "c.pdf"]);
+        assert_eq!(*report[0].1.as_ref().unwrap(), single.len());
+        assert!(report[1].1.is_err());
+        assert_eq!(*report[2].1.as_ref().unwrap(), single.len());
+
+        // Examples follow the filename order
+        let inputs: Vec<&str> = kb.get_examples().iter().map(|e| e.input.as_str()).collect();
+        let expected: Vec<&str> = single
+            .iter()
+            .chain(single.iter())
+            .map(|e| e.input.as_str())
+            .collect();
+        assert_eq!(inputs, expected);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_overlap_not_smaller_than_max_chunk_size() {
+        for overlap in [20, 50] {
+            let config = PdfLoaderConfig {
+                min_chunk_size: 1,
+                max_chunk_size: 20,
+                chunk_overlap: overlap,
+                split_by_sentence: false,
+                ..Default::default()
+            };
+            assert!(matches!(
+                config.effective_chunk_overlap(),
+                Err(PdfError::InvalidConfig(_))
+            ));
+
+            let loader = PdfLoader::with_config(config);
+            assert!(matches!(
+                loader.pdf_to_training_examples("missing.pdf"),
+                Err(PdfError::InvalidConfig(_))
+            ));
+
+            // Plain text conversion clamps the overlap instead of panicking
+            let chunks = loader.split_text_into_chunks(&"abcdefghij".repeat(5));
+            assert!(chunks.len() > 1);
+            assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 20));
+        }
+    }
+
+    #[test]
+    fn test_sentence_segmenter() {
+        let text = "สวัสดีครับ วันนี้อากาศดี\n今天天气很好 我们去公园\nNo punctuation here either\nLast line. Two sentences.";
+
+        let lines = PdfLoader::with_config(PdfLoaderConfig {
+            sentence_segmenter: SentenceSegmenter::Lines,
+            ..Default::default()
+        });
+        assert_eq!(
+            lines.split_into_sentences(text),
+            vec![
+                "สวัสดีครับ วันนี้อากาศดี",
+                "今天天气很好 我们去公园",
+                "No punctuation here either",
+                "Last line. Two sentences.",
+            ]
+        );
+        assert_eq!(lines.sentence_segments(text).concat(), text);
+
+        // Punctuation stays the default and ignores line breaks
+        let punctuation = PdfLoader::new().split_into_sentences(text);
+        assert_eq!(punctuation.len(), 2);
+
+        let windows = PdfLoader::with_config(PdfLoaderConfig {
+            sentence_segmenter: SentenceSegmenter::FixedWindow(10),
+            ..Default::default()
+        });
+        let segments = windows.sentence_segments(text);
+        assert!(segments.iter().all(|s| s.chars().count() <= 10));
+        assert_eq!(segments.concat(), text);
+    }
+
+    #[test]
+    fn test_chunk_overlap_ratio() {
+        let config = PdfLoaderConfig {
+            max_chunk_size: 1000,
+            chunk_overlap_ratio: Some(0.2),
+            ..Default::default()
+        };
+        assert_eq!(config.effective_chunk_overlap().unwrap(), 200);
+
+        let config = PdfLoaderConfig {
+            max_chunk_size: 40,
+            chunk_overlap: 0,
+            chunk_overlap_ratio: Some(0.25),
+            split_by_sentence: false,
+            min_chunk_size: 1,
+            ..Default::default()
+        };
+        let chunks = PdfLoader::with_config(config).split_text_into_chunks(&"abcdefghij".repeat(8));
+        let first: Vec<char> = chunks[0].chars().collect();
+        assert!(chunks[1].starts_with(&first[30..].iter().collect::<String>()));
+
+        for ratio in [1.0, 1.5, -0.1, f32::NAN] {
+            let config = PdfLoaderConfig {
+                chunk_overlap_ratio: Some(ratio),
+                ..Default::default()
+            };
+            assert!(matches!(
+                config.effective_chunk_overlap(),
+                Err(PdfError::InvalidConfig(_))
+            ));
+            let loader = PdfLoader::with_config(config);
+            assert!(matches!(
+                loader.pdf_to_training_examples("missing.pdf"),
+                Err(PdfError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_split_by_heading() {
+        let config = PdfLoaderConfig {
+            split_by_heading: true,
+            ..Default::default()
+        };
+        let loader = PdfLoader::with_config(config);
+
+        let text = "Device manual for the X100.\n\
+                    1 Introduction\n\
+                    This manual explains the device. It covers setup.\n\
+                    1.1 Setup\n\
+                    Plug the device in. Wait for the light.\n\
+                    \n\
+                    MAINTENANCE\n\
+                    Clean the device weekly.\n\
+                    \n\
+                    Troubleshooting tips\n\
+                    \n\
+                    Restart the device if the light blinks.";
+        let examples = loader.text_to_training_examples(text);
+
+        let headings: Vec<Option<&str>> = examples
+            .iter()
+            .map(|ex| ex.metadata.as_ref().unwrap()["heading"].as_str())
+            .collect();
+        assert_eq!(
+            headings,
+            vec![
+                None,
+                Some("1 Introduction"),
+                Some("1.1 Setup"),
+                Some("MAINTENANCE"),
+                Some("Troubleshooting tips"),
+            ]
+        );
+
+        // Every chunk after the preamble starts with its heading
+        assert!(examples[2].input.starts_with("1.1 Setup"));
+        assert!(examples[2].input.contains("Wait for the light."));
+        assert!(!examples[2].input.contains("Clean the device"));
+
+        // Without the flag, the text stays in one chunk
+        let examples = PdfLoader::new().text_to_training_examples(text);
+        assert_eq!(examples.len(), 1);
+        assert!(examples[0]
+            .metadata
+            .as_ref()
+            .unwrap()
+            .get("heading")
+            .is_none());
+    }
+
     #[test]
     fn test_split_text_into_chunks() {
         let config = PdfLoaderConfig {
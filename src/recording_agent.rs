@@ -0,0 +1,156 @@
+// src/recording_agent.rs - Agent adapter that records queries for later retraining
+use crate::agent::{Agent, ResponseFormat, TrainableAgent, TrainingExample};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// A query answered by a `RecordingAgent`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedQuery {
+    /// The query as asked
+    pub query: String,
+    /// The answer that was given
+    pub response: ResponseFormat,
+    /// Confidence of the wrapped agent for the query
+    pub confidence: f32,
+}
+
+/// Wraps an agent and records every predicted query with its answer and confidence,
+/// so real traffic can be curated into training data later
+pub struct RecordingAgent<A: Agent> {
+    inner: A,
+    log: RefCell<Vec<RecordedQuery>>,
+}
+
+impl<A: Agent> RecordingAgent<A> {
+    /// Creates a new recording agent around `inner` with an empty log
+    pub fn new(inner: A) -> Self {
+        Self {
+            inner,
+            log: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the wrapped agent
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Returns the recorded queries in the order they were asked and clears the log
+    pub fn take_log(&self) -> Vec<RecordedQuery> {
+        self.log.take()
+    }
+
+    /// Appends the recorded queries to a JSONL file (one JSON object per line) and
+    /// clears the log. Returns the number of written records.
+    pub fn flush_to_jsonl<P: AsRef<Path>>(&self, path: P) -> Result<usize, String> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Error opening log file: {}", e))?;
+
+        let records = self.take_log();
+        let mut lines = String::new();
+        for record in &records {
+            let line = serde_json::to_string(record)
+                .map_err(|e| format!("Error serializing record: {}", e))?;
+            lines.push_str(&line);
+            lines.push('\n');
+        }
+        file.write_all(lines.as_bytes())
+            .map_err(|e| format!("Error writing log file: {}", e))?;
+
+        Ok(records.len())
+    }
+}
+
+impl<A: Agent> Agent for RecordingAgent<A> {
+    /// Predicts with the wrapped agent and records query, answer and confidence
+    fn predict(&self, input: &str) -> ResponseFormat {
+        let response = self.inner.predict(input);
+        self.log.borrow_mut().push(RecordedQuery {
+            query: input.to_string(),
+            response: response.clone(),
+            confidence: self.inner.confidence(input),
+        });
+        response
+    }
+
+    /// Delegates the confidence to the wrapped agent without recording
+    fn confidence(&self, input: &str) -> f32 {
+        self.inner.confidence(input)
+    }
+
+    /// Uses the confidence threshold of the wrapped agent
+    fn confidence_threshold(&self) -> f32 {
+        self.inner.confidence_threshold()
+    }
+
+    /// Returns the inputs known to the wrapped agent
+    fn known_inputs(&self) -> Vec<&str> {
+        self.inner.known_inputs()
+    }
+
+    /// Returns the formats of the wrapped agent
+    fn supported_formats(&self) -> Vec<&'static str> {
+        self.inner.supported_formats()
+    }
+}
+
+impl<A: TrainableAgent> TrainableAgent for RecordingAgent<A> {
+    /// Trains the wrapped agent with the provided training data
+    fn train(&mut self, data: &[TrainingExample]) {
+        self.inner.train(data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::match_agent::MatchAgent;
+
+    #[test]
+    fn test_records_queries_in_order_and_drains() {
+        let mut agent = RecordingAgent::new(MatchAgent::new_exact());
+        agent.train(&[TrainingExample {
+            input: "What is airust?".to_string(),
+            output: ResponseFormat::Text("A Rust library".to_string()),
+            weight: 1.0,
+            metadata: None,
+        }]);
+
+        agent.predict("What is airust?");
+        agent.predict("Who are you?");
+        // Confidence checks are not recorded
+        agent.confidence("What is airust?");
+
+        let log = agent.take_log();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].query, "What is airust?");
+        assert_eq!(String::from(log[0].response.clone()), "A Rust library");
+        assert_eq!(log[0].confidence, 1.0);
+        assert_eq!(log[1].query, "Who are you?");
+        assert_eq!(log[1].confidence, 0.0);
+        assert!(agent.take_log().is_empty());
+
+        let path =
+            std::env::temp_dir().join(format!("airust_recording_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        agent.predict("What is airust?");
+        assert_eq!(agent.flush_to_jsonl(&path).unwrap(), 1);
+        agent.predict("Who are you?");
+        assert_eq!(agent.flush_to_jsonl(&path).unwrap(), 1);
+        assert!(agent.take_log().is_empty());
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let queries: Vec<String> = content
+            .lines()
+            .map(|line| serde_json::from_str::<RecordedQuery>(line).unwrap().query)
+            .collect();
+        assert_eq!(queries, vec!["What is airust?", "Who are you?"]);
+        std::fs::remove_file(path).ok();
+    }
+}
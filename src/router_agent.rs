@@ -0,0 +1,122 @@
+// src/router_agent.rs - Agent combinator that routes queries to sub-agents
+use crate::agent::{Agent, ResponseFormat};
+
+/// Decides whether a query belongs to a route
+pub type RoutePredicate = Box<dyn Fn(&str) -> bool>;
+
+/// Routes each query to the first agent whose predicate matches, e.g. one agent per
+/// knowledge domain selected by keywords. Unmatched queries go to the default agent.
+pub struct RouterAgent {
+    routes: Vec<(RoutePredicate, Box<dyn Agent>)>,
+    default: Box<dyn Agent>,
+}
+
+impl RouterAgent {
+    /// Creates a router without routes that answers everything with `default`
+    pub fn new(default: Box<dyn Agent>) -> Self {
+        Self {
+            routes: Vec::new(),
+            default,
+        }
+    }
+
+    /// Adds a route after the existing ones; earlier routes take precedence
+    pub fn with_route(mut self, predicate: RoutePredicate, agent: Box<dyn Agent>) -> Self {
+        self.routes.push((predicate, agent));
+        self
+    }
+
+    /// Returns the agent responsible for `input`
+    fn route(&self, input: &str) -> &dyn Agent {
+        self.routes
+            .iter()
+            .find(|(predicate, _)| predicate(input))
+            .map_or(self.default.as_ref(), |(_, agent)| agent.as_ref())
+    }
+}
+
+impl Agent for RouterAgent {
+    /// Predicts with the agent selected for the input
+    fn predict(&self, input: &str) -> ResponseFormat {
+        self.route(input).predict(input)
+    }
+
+    /// Reports the confidence of the agent selected for the input
+    fn confidence(&self, input: &str) -> f32 {
+        self.route(input).confidence(input)
+    }
+
+    /// Applies the confidence threshold of the agent selected for the input
+    fn can_answer(&self, input: &str) -> bool {
+        self.route(input).can_answer(input)
+    }
+
+    /// Returns the inputs known to all routed agents and the default agent
+    fn known_inputs(&self) -> Vec<&str> {
+        self.routes
+            .iter()
+            .flat_map(|(_, agent)| agent.known_inputs())
+            .chain(self.default.known_inputs())
+            .collect()
+    }
+
+    /// Returns every format any routed agent or the default agent can return
+    fn supported_formats(&self) -> Vec<&'static str> {
+        let mut formats: Vec<&'static str> = Vec::new();
+        let agents = self
+            .routes
+            .iter()
+            .map(|(_, agent)| agent)
+            .chain(std::iter::once(&self.default));
+        for agent in agents {
+            for format in agent.supported_formats() {
+                if !formats.contains(&format) {
+                    formats.push(format);
+                }
+            }
+        }
+        formats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::const_agent::ConstAgent;
+
+    fn agent_answering(answer: &'static str) -> Box<dyn Agent> {
+        Box::new(ConstAgent::new(answer, 1.0))
+    }
+
+    fn keyword(word: &'static str) -> RoutePredicate {
+        Box::new(move |input: &str| input.to_lowercase().contains(word))
+    }
+
+    #[test]
+    fn test_routes_by_predicate() {
+        let router = RouterAgent::new(agent_answering("general"))
+            .with_route(keyword("invoice"), agent_answering("billing"))
+            .with_route(keyword("password"), agent_answering("accounts"))
+            .with_route(keyword("invoice"), agent_answering("never reached"));
+
+        assert_eq!(router.predict_text("Where is my Invoice?"), "billing");
+        assert_eq!(router.predict_text("reset password"), "accounts");
+        assert_eq!(
+            router.predict_text("What are the opening hours?"),
+            "general"
+        );
+    }
+
+    #[test]
+    fn test_confidence_follows_selected_agent() {
+        let router = RouterAgent::new(Box::new(ConstAgent::new("general", 0.2))).with_route(
+            keyword("invoice"),
+            Box::new(ConstAgent::new("billing", 0.9)),
+        );
+
+        assert_eq!(router.confidence("my invoice"), 0.9);
+        assert!(router.can_answer("my invoice"));
+        assert_eq!(router.confidence("opening hours"), 0.2);
+        assert!(!router.can_answer("opening hours"));
+    }
+}
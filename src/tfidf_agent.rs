@@ -1,9 +1,20 @@
 // src/tfidf_agent.rs - Optimized TF-IDF/BM25 Agent
-use crate::agent::{text_utils, Agent, ResponseFormat, TrainableAgent, TrainingExample};
+use crate::agent::{
+    limit_input, supported_formats_of, text_utils, truncation_metadata, Agent, InputPreprocessor,
+    PredictionResult, ResponseFormat, TrainableAgent, TrainingExample,
+    DEFAULT_CONFIDENCE_THRESHOLD, EMPTY_QUERY_RESPONSE,
+};
+use crate::knowledge::check_version;
 use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+#[cfg(feature = "timing")]
+use std::sync::Mutex;
 
 /// TF-IDF Agent using BM25 scoring for intelligent text matching
+#[derive(Serialize, Deserialize)]
 pub struct TfidfAgent {
     /// Stored training documents
     docs: Vec<TrainingExample>,
@@ -17,35 +28,656 @@ pub struct TfidfAgent {
     /// Total number of documents
     doc_count: f32,
 
-    /// BM25 parameter k1 (controls term frequency scaling)
-    bm25_k1: f32,
+    /// Scoring function used to rank documents
+    scoring: ScoringFn,
 
-    /// BM25 parameter b (controls document length normalization)
-    bm25_b: f32,
+    /// Language whose stopwords are not trusted as evidence for a match
+    stopword_lang: String,
+
+    /// Whether stopwords are dropped from documents and queries
+    #[serde(default)]
+    remove_stopwords: bool,
+
+    /// Whether consecutive word pairs are indexed alongside single words
+    use_bigrams: bool,
+
+    /// Whether terms keep their case (e.g. to tell acronyms from ordinary words)
+    case_sensitive: bool,
+
+    /// Answers with fewer tokens than this are scored down proportionally (0 disables)
+    min_answer_length: usize,
+
+    /// Whether training sorts the examples by input for a reproducible index
+    stable_ordering: bool,
+
+    /// Maximum length of answers returned for retrieval examples (input == output)
+    retrieval_snippet_chars: Option<usize>,
+
+    /// How example weights influence the ranking
+    #[serde(default)]
+    weight_mode: WeightMode,
+
+    /// Inverse document frequency formula of BM25 and BM25+
+    #[serde(default)]
+    idf_variant: IdfVariant,
+
+    /// Below this many documents BM25 falls back to counting matching terms (0 disables)
+    #[serde(default = "default_min_bm25_docs")]
+    min_bm25_docs: usize,
+
+    /// Whether examples with an empty or whitespace-only answer are left out of the ranking
+    #[serde(default)]
+    skip_empty_outputs: bool,
+
+    /// Minimum number of distinct query terms a document must contain to match
+    #[serde(default)]
+    min_term_matches: usize,
+
+    /// Confidence an answer must exceed for `can_answer`
+    #[serde(default = "default_confidence_threshold")]
+    confidence_threshold: f32,
+
+    /// Queries longer than this many characters are truncated before tokenizing
+    #[serde(default)]
+    max_input_chars: Option<usize>,
+
+    /// Optional normalization applied to documents and queries before tokenizing.
+    /// Not serialized; set it again after loading an index.
+    #[serde(skip)]
+    input_preprocessor: Option<InputPreprocessor>,
+
+    /// Durations of the most recent ranking
+    #[cfg(feature = "timing")]
+    #[serde(skip)]
+    last_timing: Mutex<Option<PredictTiming>>,
+}
+
+/// Time spent in the phases of one ranking, as reported by `TfidfAgent::last_timing`
+#[cfg(feature = "timing")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PredictTiming {
+    /// Turning the query into terms
+    pub tokenize: std::time::Duration,
+    /// Scoring all documents
+    pub score: std::time::Duration,
+    /// Sorting the matches
+    pub sort: std::time::Duration,
+}
+
+/// Scoring functions available for ranking documents against a query
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ScoringFn {
+    /// Okapi BM25 with term frequency scaling `k1` and length normalization `b`
+    Bm25 { k1: f32, b: f32 },
+    /// BM25+ which adds `delta` for every matching term, so long documents are not
+    /// scored below short ones merely because of their length. `delta: 0.0` equals `Bm25`.
+    Bm25Plus { k1: f32, b: f32, delta: f32 },
+    /// Cosine similarity between TF-IDF weighted query and document vectors
+    TfIdfCosine,
+    /// Number of distinct query terms occurring in the document
+    CountOverlap,
+}
+
+/// How the weight of an example influences its ranking score
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WeightMode {
+    /// The score is multiplied by the weight, so a high weight can outrank relevance
+    #[default]
+    Multiplicative,
+    /// Matching documents get a small bonus growing with the weight, which settles
+    /// close calls without overriding relevance
+    AdditivePrior,
+    /// Weights are not used for ranking
+    Ignore,
+}
+
+/// Inverse document frequency formulas for BM25, to match the scores of a reference
+/// implementation. `N` is the number of documents, `df` the number containing the term.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdfVariant {
+    /// `ln(1 + (N - df + 0.5) / (df + 0.5))` as in Lucene and Elasticsearch; never negative
+    #[default]
+    Lucene,
+    /// Classic Robertson-Spärck-Jones `ln((N - df + 0.5) / (df + 0.5))`, negative for
+    /// terms in more than half of the documents
+    RobertsonSparckJones,
+    /// Robertson-Spärck-Jones clamped at 0, so very common terms add nothing
+    RobertsonSparckJonesClamped,
+}
+
+impl IdfVariant {
+    /// Computes the IDF of a term occurring in `df` of `doc_count` documents
+    pub fn idf(self, df: f32, doc_count: f32) -> f32 {
+        let odds = (doc_count - df + 0.5) / (df + 0.5);
+        match self {
+            IdfVariant::Lucene => (1.0 + odds).ln(),
+            IdfVariant::RobertsonSparckJones => odds.ln(),
+            IdfVariant::RobertsonSparckJonesClamped => odds.ln().max(0.0),
+        }
+    }
+}
+
+impl Default for ScoringFn {
+    fn default() -> Self {
+        ScoringFn::Bm25 { k1: 1.2, b: 0.75 }
+    }
+}
+
+/// All options of a `TfidfAgent` in one serializable place.
+/// Missing fields take their default values when deserializing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TfidfConfig {
+    /// Scoring function used to rank documents
+    pub scoring: ScoringFn,
+    /// Language whose stopwords are not trusted as evidence for a match
+    pub stopword_lang: String,
+    /// Whether stopwords are dropped from documents and queries
+    pub remove_stopwords: bool,
+    /// Whether consecutive word pairs are indexed alongside single words
+    pub use_bigrams: bool,
+    /// Whether terms keep their case
+    pub case_sensitive: bool,
+    /// Answers with fewer tokens than this are scored down proportionally (0 disables)
+    pub min_answer_length: usize,
+    /// Whether training sorts the examples by input for a reproducible index
+    pub stable_ordering: bool,
+    /// Maximum length of answers returned for retrieval examples (input == output)
+    pub retrieval_snippet_chars: Option<usize>,
+    /// How example weights influence the ranking
+    pub weight_mode: WeightMode,
+    /// Inverse document frequency formula of BM25 and BM25+
+    pub idf_variant: IdfVariant,
+    /// Below this many documents BM25 falls back to counting matching terms (0 disables)
+    pub min_bm25_docs: usize,
+    /// Whether examples with an empty or whitespace-only answer are left out of the ranking
+    pub skip_empty_outputs: bool,
+    /// Minimum number of distinct query terms a document must contain to match
+    pub min_term_matches: usize,
+    /// Confidence an answer must exceed for `can_answer`
+    pub confidence_threshold: f32,
+    /// Queries longer than this many characters are truncated before tokenizing
+    pub max_input_chars: Option<usize>,
+}
+
+impl Default for TfidfConfig {
+    fn default() -> Self {
+        Self {
+            scoring: ScoringFn::default(),
+            stopword_lang: "en".to_string(),
+            remove_stopwords: false,
+            use_bigrams: false,
+            case_sensitive: false,
+            min_answer_length: 0,
+            stable_ordering: false,
+            retrieval_snippet_chars: None,
+            weight_mode: WeightMode::default(),
+            idf_variant: IdfVariant::default(),
+            min_bm25_docs: DEFAULT_MIN_BM25_DOCS,
+            skip_empty_outputs: false,
+            min_term_matches: 0,
+            confidence_threshold: DEFAULT_CONFIDENCE_THRESHOLD,
+            max_input_chars: None,
+        }
+    }
+}
+
+/// Size of a trained index, as reported by `TfidfAgent::index_size`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexStats {
+    /// Number of indexed documents
+    pub documents: usize,
+    /// Number of distinct terms in the vocabulary
+    pub unique_terms: usize,
+    /// Number of (document, term) entries over all documents
+    pub total_postings: usize,
+    /// Rough estimate of the heap and inline memory held by the index, in bytes
+    pub approx_bytes: usize,
+}
+
+/// Serde default for indices saved before the confidence threshold existed
+fn default_confidence_threshold() -> f32 {
+    DEFAULT_CONFIDENCE_THRESHOLD
+}
+
+/// Serde default for indices saved before the tiny-corpus fallback existed
+fn default_min_bm25_docs() -> usize {
+    DEFAULT_MIN_BM25_DOCS
+}
+
+/// Corpus size below which BM25 falls back to counting matching terms by default
+const DEFAULT_MIN_BM25_DOCS: usize = 3;
+
+/// Upper bound for the confidence of matches that rely on stopwords only
+const STOPWORD_ONLY_CONFIDENCE: f32 = 0.05;
+
+/// Scale of the bonus `WeightMode::AdditivePrior` adds per ln(1 + weight)
+const WEIGHT_PRIOR_SCALE: f32 = 0.1;
+
+/// Jaccard similarity at which two answers count as near-duplicates in `predict_distinct`
+const DUPLICATE_SIMILARITY_THRESHOLD: f32 = 0.7;
+
+/// Splits text into trimmed sentences ending in '.', '!' or '?' followed by whitespace
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((idx, c)) = chars.next() {
+        let at_boundary = chars.peek().is_none_or(|&(_, next)| next.is_whitespace());
+        if matches!(c, '.' | '!' | '?') && at_boundary {
+            let end = idx + c.len_utf8();
+            sentences.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    sentences.push(text[start..].trim());
+
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
 }
 
 impl TfidfAgent {
     /// Creates a new TF-IDF agent with default BM25 parameters
     pub fn new() -> Self {
+        Self::from_config(TfidfConfig::default())
+    }
+
+    /// Creates an untrained agent with all options taken from `config`
+    pub fn from_config(config: TfidfConfig) -> Self {
         Self {
             docs: Vec::new(),
             term_df: IndexMap::new(),
             doc_term_freq: Vec::new(),
             doc_count: 0.0,
-            bm25_k1: 1.2, // Default term frequency scaling
-            bm25_b: 0.75, // Default length normalization
+            scoring: config.scoring,
+            stopword_lang: config.stopword_lang,
+            remove_stopwords: config.remove_stopwords,
+            use_bigrams: config.use_bigrams,
+            case_sensitive: config.case_sensitive,
+            min_answer_length: config.min_answer_length,
+            stable_ordering: config.stable_ordering,
+            retrieval_snippet_chars: config.retrieval_snippet_chars,
+            weight_mode: config.weight_mode,
+            idf_variant: config.idf_variant,
+            min_bm25_docs: config.min_bm25_docs,
+            skip_empty_outputs: config.skip_empty_outputs,
+            min_term_matches: config.min_term_matches,
+            confidence_threshold: config.confidence_threshold,
+            max_input_chars: config.max_input_chars,
+            input_preprocessor: None,
+            #[cfg(feature = "timing")]
+            last_timing: Mutex::new(None),
+        }
+    }
+
+    /// Returns the current options, e.g. to store them next to the knowledge base
+    pub fn config(&self) -> TfidfConfig {
+        TfidfConfig {
+            scoring: self.scoring,
+            stopword_lang: self.stopword_lang.clone(),
+            remove_stopwords: self.remove_stopwords,
+            use_bigrams: self.use_bigrams,
+            case_sensitive: self.case_sensitive,
+            min_answer_length: self.min_answer_length,
+            stable_ordering: self.stable_ordering,
+            retrieval_snippet_chars: self.retrieval_snippet_chars,
+            weight_mode: self.weight_mode,
+            idf_variant: self.idf_variant,
+            min_bm25_docs: self.min_bm25_docs,
+            skip_empty_outputs: self.skip_empty_outputs,
+            min_term_matches: self.min_term_matches,
+            confidence_threshold: self.confidence_threshold,
+            max_input_chars: self.max_input_chars,
+        }
+    }
+
+    /// Returns at most `max_chars` characters for retrieval examples (input == output,
+    /// e.g. PDF chunks) instead of the whole chunk. Question/answer examples are unaffected.
+    pub fn with_retrieval_snippets(mut self, max_chars: usize) -> Self {
+        self.retrieval_snippet_chars = Some(max_chars);
+        self
+    }
+
+    /// Returns the answer of a document, shortened for retrieval examples if configured
+    fn answer(&self, idx: usize) -> ResponseFormat {
+        let doc = &self.docs[idx];
+        match (self.retrieval_snippet_chars, &doc.output) {
+            (Some(max_chars), ResponseFormat::Text(text)) if doc.is_retrieval() => {
+                ResponseFormat::Text(text_utils::truncate_chars_with_ellipsis(text, max_chars))
+            }
+            (Some(max_chars), ResponseFormat::Markdown(md)) if doc.is_retrieval() => {
+                ResponseFormat::Markdown(text_utils::truncate_chars_with_ellipsis(md, max_chars))
+            }
+            _ => doc.output.clone(),
+        }
+    }
+
+    /// Sorts examples by input (then output) when training, so equivalent datasets in any
+    /// order produce identical indices. Document indices then no longer follow the
+    /// caller's order. Incremental updates via `apply_diff` are appended unsorted.
+    pub fn with_stable_ordering(mut self, enabled: bool) -> Self {
+        self.stable_ordering = enabled;
+        self
+    }
+
+    /// Chooses the IDF formula of BM25 and BM25+ (default: `IdfVariant::Lucene`)
+    pub fn with_idf_variant(mut self, variant: IdfVariant) -> Self {
+        self.idf_variant = variant;
+        self
+    }
+
+    /// Scores with the number of matching query terms instead of BM25 while the index
+    /// holds fewer than `min_docs` documents. In such tiny corpora most terms occur in
+    /// every document, so IDF values are degenerate (zero with the Robertson-Spärck-Jones
+    /// variants) and rankings close to random. 0 disables the fallback; the default is 3.
+    pub fn with_min_bm25_docs(mut self, min_docs: usize) -> Self {
+        self.min_bm25_docs = min_docs;
+        self
+    }
+
+    /// Chooses how example weights influence the ranking (default: multiplicative)
+    pub fn with_weight_mode(mut self, mode: WeightMode) -> Self {
+        self.weight_mode = mode;
+        self
+    }
+
+    /// Sets the confidence an answer must exceed for `can_answer` (default: 0.5)
+    pub fn with_confidence_threshold(mut self, threshold: f32) -> Self {
+        self.confidence_threshold = threshold;
+        self
+    }
+
+    /// Truncates queries to `max_chars` characters before tokenizing, so pasted walls of
+    /// text do not make scoring slow (default: unlimited). `predict_with_metadata` flags
+    /// truncated queries with `input_truncated`.
+    pub fn with_max_input_chars(mut self, max_chars: usize) -> Self {
+        self.max_input_chars = Some(max_chars);
+        self
+    }
+
+    /// Leaves examples with an empty or whitespace-only answer (e.g. noisy PDF chunks) out
+    /// of the ranking, so the next best non-empty answer is returned instead
+    pub fn with_skip_empty_outputs(mut self, enabled: bool) -> Self {
+        self.skip_empty_outputs = enabled;
+        self
+    }
+
+    /// Excludes documents containing fewer than `min_terms` distinct query terms, so a
+    /// single incidental term no longer makes a match. Queries with fewer distinct terms
+    /// than `min_terms` match nothing. 0 and 1 accept any positive score.
+    pub fn with_min_term_matches(mut self, min_terms: usize) -> Self {
+        self.min_term_matches = min_terms;
+        self
+    }
+
+    /// Applies the example weight to a relevance score according to the weight mode
+    fn weighted_score(&self, score: f32, weight: f32) -> f32 {
+        match self.weight_mode {
+            WeightMode::Multiplicative => score * weight,
+            // Only matching documents get the prior, so weight alone never makes a match
+            WeightMode::AdditivePrior if score > 0.0 => {
+                score + WEIGHT_PRIOR_SCALE * weight.max(0.0).ln_1p()
+            }
+            WeightMode::AdditivePrior | WeightMode::Ignore => score,
         }
     }
 
+    /// Scores answers shorter than `min_tokens` down in proportion to their length,
+    /// so substantive answers win when relevance is otherwise close
+    pub fn with_min_answer_length(mut self, min_tokens: usize) -> Self {
+        self.min_answer_length = min_tokens;
+        self
+    }
+
+    /// Dampening factor (0.0 - 1.0) for answers below the minimum length
+    fn answer_length_factor(&self, doc: &TrainingExample) -> f32 {
+        if self.min_answer_length == 0 {
+            return 1.0;
+        }
+
+        let answer_tokens = text_utils::tokenize(&doc.output.to_string()).len();
+        (answer_tokens as f32 / self.min_answer_length as f32).min(1.0)
+    }
+
+    /// Keeps the case of terms so that e.g. "US" and "us" are indexed separately.
+    /// Must be set before training.
+    pub fn with_case_sensitive(mut self, enabled: bool) -> Self {
+        self.case_sensitive = enabled;
+        self
+    }
+
+    /// Indexes word bigrams in addition to single words, rewarding exact phrase matches.
+    /// Must be set before training.
+    pub fn with_bigrams(mut self, enabled: bool) -> Self {
+        self.use_bigrams = enabled;
+        self
+    }
+
+    /// Turns text into index terms according to the configured options (input
+    /// preprocessor, case, stopwords, bigrams). This is the only preprocessing path:
+    /// training and querying both go through it, so any option applies to documents
+    /// and queries alike.
+    pub fn preprocess(&self, text: &str) -> Vec<String> {
+        let normalized;
+        let text = match &self.input_preprocessor {
+            Some(preprocessor) => {
+                normalized = preprocessor(text);
+                normalized.as_str()
+            }
+            None => text,
+        };
+
+        let mut terms = if self.case_sensitive {
+            text_utils::tokenize_preserving_case(text)
+        } else {
+            text_utils::tokenize(text)
+        };
+        if self.remove_stopwords {
+            terms.retain(|term| !text_utils::is_stopword(term, &self.stopword_lang));
+        }
+        if self.use_bigrams {
+            let bigrams = text_utils::word_ngrams(&terms, 2);
+            terms.extend(bigrams);
+        }
+        terms
+    }
+
+    /// Normalizes documents and queries with `preprocessor` before tokenizing, e.g. to strip
+    /// markdown. Must be set before training.
+    pub fn with_input_preprocessor(mut self, preprocessor: InputPreprocessor) -> Self {
+        self.input_preprocessor = Some(preprocessor);
+        self
+    }
+
+    /// Drops stopwords of the configured language from documents and queries.
+    /// Must be set before training.
+    pub fn with_stopword_removal(mut self, enabled: bool) -> Self {
+        self.remove_stopwords = enabled;
+        self
+    }
+
+    /// Sets the language used to recognize stopwords (e.g. "en", "de")
+    pub fn with_language(mut self, lang: &str) -> Self {
+        self.stopword_lang = lang.to_string();
+        self
+    }
+
     /// Configures custom BM25 parameters for fine-tuned matching
     pub fn with_bm25_params(mut self, k1: f32, b: f32) -> Self {
-        self.bm25_k1 = k1;
-        self.bm25_b = b;
+        self.scoring = ScoringFn::Bm25 { k1, b };
+        self
+    }
+
+    /// Uses BM25+ scoring with a lower bound `delta` for the contribution of matching terms
+    pub fn with_bm25_plus(mut self, k1: f32, b: f32, delta: f32) -> Self {
+        self.scoring = ScoringFn::Bm25Plus { k1, b, delta };
+        self
+    }
+
+    /// Selects the scoring function; the trained index is kept, so no retraining is needed
+    pub fn with_scoring(mut self, scoring: ScoringFn) -> Self {
+        self.scoring = scoring;
         self
     }
 
-    /// Calculates BM25 score between query terms and a specific document
-    fn bm25_score(&self, query_terms: &[String], doc_idx: usize) -> f32 {
+    /// Saves the trained index to a JSON file, stamped with the crate version
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let artifact = IndexArtifactRef {
+            version: crate::VERSION,
+            index: self,
+        };
+        let json = serde_json::to_string_pretty(&artifact)
+            .map_err(|e| format!("Serialization error: {}", e))?;
+
+        fs::write(path, json).map_err(|e| format!("Failed to write to file: {}", e))
+    }
+
+    /// Loads a trained index from a JSON file, warning if it was saved by another version
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        Self::load_checked(path.as_ref(), false)
+    }
+
+    /// Loads a trained index from a JSON file, failing if it was saved by another version
+    pub fn load_strict<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        Self::load_checked(path.as_ref(), true)
+    }
+
+    fn load_checked(path: &Path, strict: bool) -> Result<Self, String> {
+        let data = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+
+        let artifact: IndexArtifact =
+            serde_json::from_str(&data).map_err(|e| format!("Deserialization error: {}", e))?;
+        check_version(&artifact.version, strict)?;
+
+        Ok(artifact.index)
+    }
+
+    /// Adds a single document to the index
+    fn index_document(&mut self, doc: TrainingExample) {
+        // Tokenize document input
+        let terms = self.preprocess(&doc.input);
+        self.index_terms(doc, terms);
+    }
+
+    /// Adds a document to the index using terms produced by the caller.
+    /// Negative and NaN weights are stored as 0.0 so they cannot produce NaN or
+    /// negative scores. Only `WeightMode::Multiplicative` drops such documents; with
+    /// `AdditivePrior` and `Ignore` they still match on relevance alone.
+    pub(crate) fn index_terms(&mut self, mut doc: TrainingExample, terms: Vec<String>) {
+        if doc.weight.is_nan() || doc.weight < 0.0 {
+            doc.weight = 0.0;
+        }
+
+        let mut doc_terms: IndexMap<String, f32> = IndexMap::new();
+
+        // Count term frequencies
+        for term in terms {
+            *doc_terms.entry(term).or_insert(0.0) += 1.0;
+        }
+
+        // Track unique terms for document frequency
+        for term in doc_terms.keys() {
+            *self.term_df.entry(term.clone()).or_insert(0.0) += 1.0;
+        }
+
+        self.docs.push(doc);
+        self.doc_term_freq.push(doc_terms);
+        self.doc_count += 1.0;
+    }
+
+    /// Updates the index with added and removed documents without retraining everything.
+    ///
+    /// Removals happen first and refer to indices before the update; out-of-range
+    /// indices are ignored. Documents after a removed one shift down by one, so stored
+    /// document indices may change, and term order in the index can differ from a
+    /// fresh `train` on the same corpus.
+    pub fn apply_diff(&mut self, added: &[TrainingExample], removed_indices: &[usize]) {
+        let mut removed: Vec<usize> = removed_indices
+            .iter()
+            .copied()
+            .filter(|&idx| idx < self.docs.len())
+            .collect();
+        removed.sort_unstable();
+        removed.dedup();
+
+        // Remove from the back so earlier indices stay valid
+        for &idx in removed.iter().rev() {
+            self.docs.remove(idx);
+            let doc_terms = self.doc_term_freq.remove(idx);
+            self.doc_count -= 1.0;
+
+            for term in doc_terms.keys() {
+                if let Some(df) = self.term_df.get_mut(term) {
+                    *df -= 1.0;
+                    if *df <= 0.0 {
+                        self.term_df.swap_remove(term);
+                    }
+                }
+            }
+        }
+
+        for doc in added {
+            self.index_document(doc.clone());
+        }
+    }
+
+    /// Rebuilds the document frequencies and the document count from the stored term
+    /// frequencies, without tokenizing again. Use it to repair or validate the index after
+    /// incremental changes; average document lengths are derived on every query and need
+    /// no rebuild. Returns whether the statistics had been inconsistent.
+    pub fn recompute_statistics(&mut self) -> bool {
+        let mut term_df: IndexMap<String, f32> = IndexMap::new();
+        for doc_terms in &self.doc_term_freq {
+            for term in doc_terms.keys() {
+                *term_df.entry(term.clone()).or_insert(0.0) += 1.0;
+            }
+        }
+        let doc_count = self.doc_term_freq.len() as f32;
+
+        let changed = term_df != self.term_df || doc_count != self.doc_count;
+        self.term_df = term_df;
+        self.doc_count = doc_count;
+        changed
+    }
+
+    /// Appends the documents of another trained index, e.g. to combine shards of a corpus
+    /// that were trained separately. The result equals training on both corpora in order.
+    ///
+    /// Document frequencies are summed, so IDF values (and therefore BM25 scores) over the
+    /// merged index differ from the scores within either shard. Both agents must produce
+    /// terms the same way; differing case, stopword or bigram options are rejected. An input
+    /// preprocessor cannot be compared and is the caller's responsibility.
+    pub fn merge_index(&mut self, other: &TfidfAgent) -> Result<(), String> {
+        if self.case_sensitive != other.case_sensitive
+            || self.use_bigrams != other.use_bigrams
+            || self.remove_stopwords != other.remove_stopwords
+            || (self.remove_stopwords && self.stopword_lang != other.stopword_lang)
+        {
+            return Err("Cannot merge indices built with different term options".to_string());
+        }
+
+        for (term, &df) in &other.term_df {
+            *self.term_df.entry(term.clone()).or_insert(0.0) += df;
+        }
+        self.docs.extend(other.docs.iter().cloned());
+        self.doc_term_freq
+            .extend(other.doc_term_freq.iter().cloned());
+        self.doc_count += other.doc_count;
+
+        Ok(())
+    }
+
+    /// Calculates the BM25 (or, with a positive `delta`, BM25+) score between query terms
+    /// and a specific document
+    fn bm25_score(
+        &self,
+        query_terms: &[String],
+        doc_idx: usize,
+        k1: f32,
+        b: f32,
+        delta: f32,
+    ) -> f32 {
         // Calculate average document length
         let avg_doc_len: f32 = self
             .doc_term_freq
@@ -63,8 +695,7 @@ impl TfidfAgent {
                 // Check if term exists in the document frequency index
                 if let Some(&df) = self.term_df.get(term) {
                     // Inverse Document Frequency (IDF) component
-                    let idf = (self.doc_count - df + 0.5) / (df + 0.5);
-                    let idf = (1.0 + idf).ln();
+                    let idf = self.idf_variant.idf(df, self.doc_count);
 
                     // Term Frequency (TF) with BM25 normalization
                     let tf = self.doc_term_freq[doc_idx]
@@ -72,91 +703,1781 @@ impl TfidfAgent {
                         .cloned()
                         .unwrap_or(0.0);
 
+                    if tf == 0.0 {
+                        return 0.0;
+                    }
+
                     // BM25 scoring formula
-                    let numerator = tf * (self.bm25_k1 + 1.0);
-                    let denominator = tf
-                        + self.bm25_k1 * (1.0 - self.bm25_b + self.bm25_b * doc_len / avg_doc_len);
+                    let numerator = tf * (k1 + 1.0);
+                    let denominator = tf + k1 * (1.0 - b + b * doc_len / avg_doc_len);
 
-                    idf * numerator / denominator
+                    idf * (numerator / denominator + delta)
                 } else {
                     0.0
                 }
             })
             .sum()
     }
-}
 
-impl Agent for TfidfAgent {
-    /// Predicts the most relevant response using BM25 scoring
-    fn predict(&self, input: &str) -> ResponseFormat {
-        // Handle empty training data
-        if self.docs.is_empty() {
-            return ResponseFormat::Text("No training data available.".to_string());
+    /// Smoothed inverse document frequency used for TF-IDF vectors
+    fn tfidf_idf(&self, df: f32) -> f32 {
+        ((1.0 + self.doc_count) / (1.0 + df)).ln() + 1.0
+    }
+
+    /// Calculates the cosine similarity of TF-IDF vectors of query and document
+    fn tfidf_cosine_score(&self, query_terms: &[String], doc_idx: usize) -> f32 {
+        let mut query_tf: IndexMap<&str, f32> = IndexMap::new();
+        for term in query_terms {
+            *query_tf.entry(term.as_str()).or_insert(0.0) += 1.0;
+        }
+
+        let doc_terms = &self.doc_term_freq[doc_idx];
+        let weight = |term: &str, tf: f32| -> f32 {
+            match self.term_df.get(term) {
+                Some(&df) => tf * self.tfidf_idf(df),
+                None => 0.0,
+            }
+        };
+
+        let dot: f32 = query_tf
+            .iter()
+            .filter_map(|(term, &q_tf)| {
+                doc_terms
+                    .get(*term)
+                    .map(|&d_tf| weight(term, q_tf) * weight(term, d_tf))
+            })
+            .sum();
+
+        let query_norm = query_tf
+            .iter()
+            .map(|(term, &tf)| weight(term, tf).powi(2))
+            .sum::<f32>()
+            .sqrt();
+        let doc_norm = doc_terms
+            .iter()
+            .map(|(term, &tf)| weight(term, tf).powi(2))
+            .sum::<f32>()
+            .sqrt();
+
+        if query_norm == 0.0 || doc_norm == 0.0 {
+            0.0
+        } else {
+            dot / (query_norm * doc_norm)
+        }
+    }
+
+    /// Counts the distinct query terms that occur in the document
+    fn count_overlap_score(&self, query_terms: &[String], doc_idx: usize) -> f32 {
+        let distinct: HashSet<&String> = query_terms.iter().collect();
+        distinct
+            .into_iter()
+            .filter(|term| self.doc_term_freq[doc_idx].contains_key(*term))
+            .count() as f32
+    }
+
+    /// Scores a document with the configured scoring function
+    fn score(&self, query_terms: &[String], doc_idx: usize) -> f32 {
+        // IDF is not meaningful in tiny corpora, so term overlap decides there
+        let is_bm25 = matches!(
+            self.scoring,
+            ScoringFn::Bm25 { .. } | ScoringFn::Bm25Plus { .. }
+        );
+        if is_bm25 && self.doc_count < self.min_bm25_docs as f32 {
+            return self.count_overlap_score(query_terms, doc_idx);
+        }
+
+        match self.scoring {
+            ScoringFn::Bm25 { k1, b } => self.bm25_score(query_terms, doc_idx, k1, b, 0.0),
+            ScoringFn::Bm25Plus { k1, b, delta } => {
+                self.bm25_score(query_terms, doc_idx, k1, b, delta)
+            }
+            ScoringFn::TfIdfCosine => self.tfidf_cosine_score(query_terms, doc_idx),
+            ScoringFn::CountOverlap => self.count_overlap_score(query_terms, doc_idx),
+        }
+    }
+
+    /// Checks that the index structures agree with each other and can be scored safely
+    fn is_consistent(&self) -> bool {
+        self.docs.len() == self.doc_term_freq.len()
+            && self.doc_count > 0.0
+            && self.doc_count as usize == self.docs.len()
+    }
+
+    /// Scores all documents and returns the matching ones sorted by descending score.
+    /// Empty or whitespace-only queries match nothing, whatever the preprocessing.
+    fn rank(&self, input: &str) -> Vec<(usize, f32)> {
+        // Forget the previous query so an early return cannot report stale durations
+        #[cfg(feature = "timing")]
+        self.set_last_timing(None);
+        if input.trim().is_empty() {
+            return Vec::new();
         }
+        #[cfg(feature = "timing")]
+        let start = std::time::Instant::now();
 
         // Tokenize input into terms
-        let query_terms = text_utils::tokenize(input);
+        let query_terms = self.preprocess(limit_input(input, self.max_input_chars));
+        #[cfg(feature = "timing")]
+        let tokenize = start.elapsed();
+
+        let ranked = self.rank_terms(&query_terms);
+        #[cfg(feature = "timing")]
+        if let Some(timing) = self.last_timing() {
+            self.set_last_timing(Some(PredictTiming { tokenize, ..timing }));
+        }
+        ranked
+    }
+
+    /// Response when nothing in the index matches `input`
+    fn no_match(&self, input: &str) -> ResponseFormat {
+        if input.trim().is_empty() {
+            ResponseFormat::Text(EMPTY_QUERY_RESPONSE.to_string())
+        } else {
+            ResponseFormat::Text("No matching answer found.".to_string())
+        }
+    }
+
+    /// Returns how long the phases of the most recent ranking took (by `predict`,
+    /// `confidence` or any other query method), or None before the first query.
+    /// Only available with the `timing` feature.
+    #[cfg(feature = "timing")]
+    pub fn last_timing(&self) -> Option<PredictTiming> {
+        *self
+            .last_timing
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    #[cfg(feature = "timing")]
+    fn set_last_timing(&self, timing: Option<PredictTiming>) {
+        *self
+            .last_timing
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = timing;
+    }
+
+    /// Ranks all documents against query terms produced by the caller
+    pub(crate) fn rank_terms(&self, query_terms: &[String]) -> Vec<(usize, f32)> {
+        // A malformed index yields no matches instead of panicking
+        if !self.is_consistent() {
+            return Vec::new();
+        }
+
+        #[cfg(feature = "timing")]
+        let start = std::time::Instant::now();
 
-        // Calculate BM25 scores for each document
+        // Calculate scores for each document
         let mut scores: Vec<(usize, f32)> = self
             .docs
             .iter()
             .enumerate()
+            .filter(|(_, doc)| {
+                !self.skip_empty_outputs || !doc.output.to_string().trim().is_empty()
+            })
+            .filter(|&(i, _)| {
+                self.min_term_matches <= 1
+                    || self.count_overlap_score(query_terms, i) >= self.min_term_matches as f32
+            })
             .map(|(i, doc)| {
                 // Calculate score with document weight
-                let score = self.bm25_score(&query_terms, i) * doc.weight;
+                let score = self.weighted_score(self.score(query_terms, i), doc.weight)
+                    * self.answer_length_factor(doc);
                 (i, score)
             })
+            .filter(|&(_, score)| score > 0.0)
             .collect();
+        #[cfg(feature = "timing")]
+        let score = start.elapsed();
 
         // Sort scores in descending order
         scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
 
-        // Select best matching document
-        if let Some(&(best_idx, score)) = scores.first() {
-            if score > 0.0 {
-                return self.docs[best_idx].output.clone();
+        #[cfg(feature = "timing")]
+        self.set_last_timing(Some(PredictTiming {
+            tokenize: std::time::Duration::ZERO,
+            score,
+            sort: start.elapsed() - score,
+        }));
+
+        scores
+    }
+
+    /// Returns the sentence of the best matching answer that shares the most distinct
+    /// terms with `input` (the first one on ties), joined with up to `window_sentences`
+    /// sentences before and after it, e.g. to quote a long PDF chunk instead of returning
+    /// all of it. Returns None if no document matches.
+    pub fn predict_snippet(&self, input: &str, window_sentences: usize) -> Option<String> {
+        let &(best_idx, _) = self.rank(input).first()?;
+        let answer = self.docs[best_idx].output.to_string();
+        let sentences = split_sentences(&answer);
+        if sentences.is_empty() {
+            return Some(answer);
+        }
+
+        let query_terms: HashSet<String> = self
+            .preprocess(limit_input(input, self.max_input_chars))
+            .into_iter()
+            .collect();
+        let mut best_sentence = 0;
+        let mut best_overlap = 0;
+        for (idx, sentence) in sentences.iter().enumerate() {
+            let terms: HashSet<String> = self.preprocess(sentence).into_iter().collect();
+            let overlap = terms.intersection(&query_terms).count();
+            if overlap > best_overlap {
+                best_sentence = idx;
+                best_overlap = overlap;
             }
         }
 
-        ResponseFormat::Text("No matching answer found.".to_string())
+        let start = best_sentence.saturating_sub(window_sentences);
+        let end = (best_sentence + window_sentences + 1).min(sentences.len());
+        Some(sentences[start..end].join(" "))
     }
-}
 
-impl TrainableAgent for TfidfAgent {
-    /// Trains the agent by processing training documents
-    fn train(&mut self, data: &[TrainingExample]) {
-        // Reset existing data
-        self.docs = data.to_vec();
-        self.doc_count = data.len() as f32;
-        self.term_df.clear();
-        self.doc_term_freq.clear();
+    /// Returns the fraction (0.0 - 1.0) of distinct query terms that occur in the index,
+    /// e.g. to detect out-of-domain queries before answering. 0.0 means no term is known;
+    /// a query without terms also has a coverage of 0.0.
+    pub fn query_coverage(&self, input: &str) -> f32 {
+        let terms: HashSet<String> = self
+            .preprocess(limit_input(input, self.max_input_chars))
+            .into_iter()
+            .collect();
+        if terms.is_empty() || input.trim().is_empty() {
+            return 0.0;
+        }
 
-        // Process each document
-        for doc in &self.docs {
-            // Tokenize document input
-            let mut doc_terms: IndexMap<String, f32> = IndexMap::new();
-            let terms = text_utils::tokenize(&doc.input);
-
-            // Count term frequencies
-            for term in &terms {
-                *doc_terms.entry(term.clone()).or_insert(0.0) += 1.0;
+        let known = terms
+            .iter()
+            .filter(|term| self.term_df.contains_key(*term))
+            .count();
+        known as f32 / terms.len() as f32
+    }
+
+    /// Returns the stored answer of the best match without cloning it, or None on a miss.
+    /// Unlike `predict`, retrieval answers are not shortened to snippets.
+    pub fn predict_ref(&self, input: &str) -> Option<&ResponseFormat> {
+        self.rank(input)
+            .first()
+            .map(|&(idx, _)| &self.docs[idx].output)
+    }
+
+    /// Returns every document scoring at least `min_score`, best first.
+    /// The score is reported as confidence and the example index in the metadata.
+    pub fn predict_above(&self, input: &str, min_score: f32) -> Vec<PredictionResult> {
+        self.rank(input)
+            .into_iter()
+            .filter(|&(_, score)| score >= min_score)
+            .map(|(idx, score)| PredictionResult {
+                response: self.answer(idx),
+                confidence: score,
+                metadata: Some(serde_json::json!({ "example_index": idx })),
+            })
+            .collect()
+    }
+
+    /// Returns up to `n` of the best matches, best first.
+    /// The score is reported as confidence and the example index in the metadata.
+    pub fn predict_top_n(&self, input: &str, n: usize) -> Vec<PredictionResult> {
+        self.rank(input)
+            .into_iter()
+            .take(n)
+            .map(|(idx, score)| PredictionResult {
+                response: self.answer(idx),
+                confidence: score,
+                metadata: Some(serde_json::json!({ "example_index": idx })),
+            })
+            .collect()
+    }
+
+    /// Returns up to `n` of the best matches, skipping answers that are near-duplicates
+    /// (Jaccard similarity of at least 0.7) of an answer already returned
+    pub fn predict_distinct(&self, input: &str, n: usize) -> Vec<PredictionResult> {
+        let mut results: Vec<PredictionResult> = Vec::new();
+        let mut texts: Vec<String> = Vec::new();
+
+        for (idx, score) in self.rank(input) {
+            if results.len() >= n {
+                break;
             }
 
-            // Track unique terms for document frequency
-            let unique_terms: HashSet<String> = terms.into_iter().collect();
-            for term in unique_terms {
-                *self.term_df.entry(term).or_insert(0.0) += 1.0;
+            let response = self.answer(idx);
+            let text = String::from(response.clone());
+            if texts.iter().any(|seen| {
+                text_utils::jaccard_similarity(seen, &text) >= DUPLICATE_SIMILARITY_THRESHOLD
+            }) {
+                continue;
             }
 
-            self.doc_term_freq.push(doc_terms);
+            texts.push(text);
+            results.push(PredictionResult {
+                response,
+                confidence: score,
+                metadata: Some(serde_json::json!({ "example_index": idx })),
+            });
         }
+
+        results
     }
-}
 
-// Default implementation for creating a new TF-IDF agent
+    /// Predicts like `predict`, but asks which question was meant when the scores of the
+    /// two best matches differ by less than `margin`. The clarification lists the
+    /// questions of all matches within `margin` of the best one.
+    pub fn predict_or_clarify(&self, input: &str, margin: f32) -> ResponseFormat {
+        let ranked = self.rank(input);
+        let best = match ranked.first() {
+            Some(&(_, best)) => best,
+            None => return self.predict(input),
+        };
+
+        let mut candidates: Vec<&str> = Vec::new();
+        for &(idx, score) in &ranked {
+            if best - score >= margin {
+                break;
+            }
+            let question = self.docs[idx].input.as_str();
+            if !candidates.contains(&question) {
+                candidates.push(question);
+            }
+        }
+
+        if candidates.len() < 2 {
+            return self.answer(ranked[0].0);
+        }
+
+        let options: Vec<String> = candidates.iter().map(|q| format!("- {}", q)).collect();
+        ResponseFormat::Text(format!(
+            "Did you mean one of the following?\n{}",
+            options.join("\n")
+        ))
+    }
+
+    /// Predicts like `predict`, but returns the best example's answer variant for `tag`
+    /// (see `TrainingExample::output_variant`), falling back to its default output
+    pub fn predict_with_context_tag(&self, input: &str, tag: &str) -> ResponseFormat {
+        match self.rank(input).first() {
+            Some(&(idx, _)) => self.docs[idx]
+                .output_variant(tag)
+                .unwrap_or_else(|| self.answer(idx)),
+            None => self.predict(input),
+        }
+    }
+
+    /// Predicts only among documents whose metadata satisfies `filter`.
+    /// Documents without metadata are out of scope; term statistics still cover the whole index.
+    pub fn predict_within<F>(&self, input: &str, filter: F) -> ResponseFormat
+    where
+        F: Fn(&serde_json::Value) -> bool,
+    {
+        if self.docs.is_empty() {
+            return ResponseFormat::Text("No training data available.".to_string());
+        }
+
+        let in_scope = self
+            .rank(input)
+            .into_iter()
+            .find(|&(idx, _)| self.docs[idx].metadata.as_ref().is_some_and(&filter));
+
+        match in_scope {
+            Some((idx, _)) => self.answer(idx),
+            None => self.no_match(input),
+        }
+    }
+
+    /// Combines the answers of the `n` best matching documents into one response
+    pub fn predict_merged(&self, input: &str, n: usize) -> ResponseFormat {
+        if self.docs.is_empty() {
+            return ResponseFormat::Text("No training data available.".to_string());
+        }
+
+        let parts: Vec<ResponseFormat> = self
+            .rank(input)
+            .into_iter()
+            .take(n)
+            .map(|(idx, _)| self.answer(idx))
+            .collect();
+
+        if parts.is_empty() {
+            return self.no_match(input);
+        }
+
+        ResponseFormat::join(&parts, "\n\n")
+    }
+
+    /// Reports the size of the trained index. `approx_bytes` counts string contents and
+    /// map entries but ignores allocator overhead and spare capacity, so treat it as a lower bound.
+    pub fn index_size(&self) -> IndexStats {
+        // Key, value and the hash stored by IndexMap per entry
+        let entry_bytes = |term: &String| {
+            term.len() + std::mem::size_of::<String>() + std::mem::size_of::<f32>() + 8
+        };
+
+        let vocabulary_bytes: usize = self.term_df.keys().map(entry_bytes).sum();
+        let postings_bytes: usize = self
+            .doc_term_freq
+            .iter()
+            .map(|terms| {
+                std::mem::size_of::<IndexMap<String, f32>>()
+                    + terms.keys().map(entry_bytes).sum::<usize>()
+            })
+            .sum();
+        let docs_bytes: usize = self
+            .docs
+            .iter()
+            .map(|doc| {
+                std::mem::size_of::<TrainingExample>()
+                    + doc.input.len()
+                    + doc.output.to_string().len()
+                    + doc.metadata.as_ref().map_or(0, |m| m.to_string().len())
+            })
+            .sum();
+
+        IndexStats {
+            documents: self.docs.len(),
+            unique_terms: self.term_df.len(),
+            total_postings: self.doc_term_freq.iter().map(IndexMap::len).sum(),
+            approx_bytes: vocabulary_bytes + postings_bytes + docs_bytes,
+        }
+    }
+
+    /// Returns the `n` terms with the highest TF-IDF weight in the given document.
+    /// Ties are broken alphabetically; an unknown index yields an empty list.
+    pub fn top_terms(&self, doc_index: usize, n: usize) -> Vec<(String, f32)> {
+        let doc_terms = match self.doc_term_freq.get(doc_index) {
+            Some(terms) => terms,
+            None => return Vec::new(),
+        };
+
+        let mut weighted: Vec<(String, f32)> = doc_terms
+            .iter()
+            .map(|(term, &tf)| {
+                let df = self.term_df.get(term).copied().unwrap_or(1.0);
+                (term.clone(), tf * self.tfidf_idf(df))
+            })
+            .collect();
+
+        weighted.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        weighted.truncate(n);
+        weighted
+    }
+}
+
+impl Agent for TfidfAgent {
+    /// Derives the confidence from the best BM25 score, penalizing stopword-only matches
+    fn confidence(&self, input: &str) -> f32 {
+        let (best_idx, score) = match self.rank(input).first() {
+            Some(&best) => best,
+            None => return 0.0,
+        };
+
+        // Map the unbounded score into 0.0 - 1.0
+        let confidence = score / (score + 1.0);
+
+        // Query terms that actually occur in the matched document
+        let contributing: Vec<String> = self
+            .preprocess(limit_input(input, self.max_input_chars))
+            .into_iter()
+            .filter(|term| self.doc_term_freq[best_idx].contains_key(term))
+            .map(|term| term.to_lowercase())
+            .collect();
+
+        // A bigram only counts as a stopword if both of its words are stopwords
+        let stopwords_only = contributing.iter().all(|term| {
+            term.split_whitespace()
+                .all(|word| text_utils::is_stopword(word, &self.stopword_lang))
+        });
+        if stopwords_only {
+            confidence.min(STOPWORD_ONLY_CONFIDENCE)
+        } else {
+            confidence
+        }
+    }
+
+    /// Predicts the most relevant response using BM25 scoring
+    fn predict(&self, input: &str) -> ResponseFormat {
+        // Handle empty training data
+        if self.docs.is_empty() {
+            return ResponseFormat::Text("No training data available.".to_string());
+        }
+
+        // Select best matching document
+        if let Some(&(best_idx, _)) = self.rank(input).first() {
+            return self.answer(best_idx);
+        }
+
+        self.no_match(input)
+    }
+
+    /// Predicts like `predict`, flagging queries shortened by `with_max_input_chars`
+    fn predict_with_metadata(&self, input: &str) -> PredictionResult {
+        PredictionResult {
+            response: self.predict(input),
+            confidence: self.confidence(input),
+            metadata: truncation_metadata(input, self.max_input_chars),
+        }
+    }
+
+    /// Returns the configured threshold for `can_answer`
+    fn confidence_threshold(&self) -> f32 {
+        self.confidence_threshold
+    }
+
+    /// Returns the inputs of the indexed documents in index order
+    fn known_inputs(&self) -> Vec<&str> {
+        self.docs.iter().map(|doc| doc.input.as_str()).collect()
+    }
+
+    /// Returns "Text" plus the formats of the indexed answers
+    fn supported_formats(&self) -> Vec<&'static str> {
+        supported_formats_of(&self.docs)
+    }
+}
+
+impl TrainableAgent for TfidfAgent {
+    /// Trains the agent by processing training documents
+    fn train(&mut self, data: &[TrainingExample]) {
+        // Reset existing data
+        self.docs.clear();
+        self.doc_count = 0.0;
+        self.term_df.clear();
+        self.doc_term_freq.clear();
+
+        let mut data = data.to_vec();
+        if self.stable_ordering {
+            data.sort_by(|a, b| {
+                a.input
+                    .cmp(&b.input)
+                    .then_with(|| a.output.to_string().cmp(&b.output.to_string()))
+            });
+        }
+
+        // Process each document
+        for doc in data {
+            self.index_document(doc);
+        }
+    }
+}
+
+/// Serialized layout of a saved index
+#[derive(Serialize)]
+struct IndexArtifactRef<'a> {
+    version: &'a str,
+    index: &'a TfidfAgent,
+}
+
+#[derive(Deserialize)]
+struct IndexArtifact {
+    version: String,
+    index: TfidfAgent,
+}
+
+// Default implementation for creating a new TF-IDF agent
 impl Default for TfidfAgent {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context_agent::ContextAgent;
+
+    fn example(input: &str, output: ResponseFormat) -> TrainingExample {
+        TrainingExample {
+            input: input.to_string(),
+            output,
+            weight: 1.0,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_predict_distinct() {
+        let chunk = |text: &str| example(text, ResponseFormat::Text(text.into()));
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            chunk("the pump must be primed before the first start of the system"),
+            chunk("pump must be primed before the first start of the system today"),
+            chunk("the pump must be primed before the first start of the system"),
+            chunk("a dry pump start damages the seals of the pump"),
+            chunk("filters are changed every month"),
+        ]);
+
+        let all = agent.predict_above("pump start", 0.0);
+        assert_eq!(all.len(), 4);
+
+        let distinct = agent.predict_distinct("pump start", 3);
+        assert_eq!(distinct.len(), 2);
+        let texts: Vec<String> = distinct
+            .iter()
+            .map(|r| String::from(r.response.clone()))
+            .collect();
+        assert!(texts.iter().any(|t| t.contains("seals")));
+        assert_eq!(texts.iter().filter(|t| t.contains("primed")).count(), 1);
+
+        assert_eq!(agent.predict_distinct("pump start", 1).len(), 1);
+    }
+
+    #[test]
+    fn test_preprocessing_is_symmetric() {
+        let text = "Rust Ownership and Rust borrowing";
+        let configs = [
+            TfidfAgent::new(),
+            TfidfAgent::new().with_case_sensitive(true),
+            TfidfAgent::new().with_bigrams(true),
+            TfidfAgent::new()
+                .with_case_sensitive(true)
+                .with_bigrams(true),
+            TfidfAgent::new().with_language("de"),
+            TfidfAgent::new().with_stopword_removal(true),
+            TfidfAgent::new()
+                .with_stopword_removal(true)
+                .with_case_sensitive(true)
+                .with_bigrams(true),
+        ];
+
+        for mut agent in configs {
+            agent.train(&[example(text, ResponseFormat::Text("doc".into()))]);
+
+            // The indexed terms are exactly the terms a query with the same text produces
+            let query: HashSet<String> = agent.preprocess(text).into_iter().collect();
+            let indexed: HashSet<String> = agent.doc_term_freq[0].keys().cloned().collect();
+            assert_eq!(query, indexed);
+            assert_eq!(
+                agent.doc_term_freq[0].values().sum::<f32>() as usize,
+                agent.preprocess(text).len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config = TfidfConfig {
+            scoring: ScoringFn::CountOverlap,
+            stopword_lang: "de".to_string(),
+            remove_stopwords: false,
+            use_bigrams: true,
+            case_sensitive: true,
+            min_answer_length: 3,
+            stable_ordering: true,
+            retrieval_snippet_chars: Some(10),
+            weight_mode: WeightMode::Ignore,
+            idf_variant: IdfVariant::RobertsonSparckJonesClamped,
+            min_bm25_docs: 3,
+            skip_empty_outputs: true,
+            min_term_matches: 1,
+            confidence_threshold: 0.7,
+            max_input_chars: Some(500),
+        };
+        let mut agent = TfidfAgent::from_config(config.clone());
+        assert_eq!(agent.config(), config);
+
+        // The builders produce the same configuration
+        let built = TfidfAgent::new()
+            .with_scoring(ScoringFn::CountOverlap)
+            .with_language("de")
+            .with_bigrams(true)
+            .with_case_sensitive(true)
+            .with_min_answer_length(3)
+            .with_stable_ordering(true)
+            .with_retrieval_snippets(10)
+            .with_weight_mode(WeightMode::Ignore)
+            .with_idf_variant(IdfVariant::RobertsonSparckJonesClamped)
+            .with_min_bm25_docs(3)
+            .with_skip_empty_outputs(true)
+            .with_min_term_matches(1)
+            .with_confidence_threshold(0.7)
+            .with_max_input_chars(500);
+        assert_eq!(built.config(), config);
+
+        agent.train(&[
+            example(
+                "the US economy grows steadily",
+                ResponseFormat::Text("the US economy grows steadily".into()),
+            ),
+            example("zeta", ResponseFormat::Text("a".into())),
+        ]);
+
+        // Stable ordering sorted the examples, case sensitivity kept "US", bigrams are indexed
+        assert_eq!(agent.docs[0].input, "the US economy grows steadily");
+        assert!(agent.doc_term_freq[0].contains_key("US"));
+        assert!(agent.doc_term_freq[0].contains_key("US economy"));
+        // Count overlap scoring and retrieval snippets
+        let results = agent.predict_above("US economy", 0.0);
+        assert_eq!(results[0].confidence, 3.0);
+        assert_eq!(String::from(results[0].response.clone()), "the US ec…");
+        // German stopwords: "the" is not a stopword there, so it counts as evidence
+        assert!(agent.confidence("the") > STOPWORD_ONLY_CONFIDENCE);
+
+        // Partial configs fill in defaults
+        let parsed: TfidfConfig = serde_json::from_str(r#"{"use_bigrams": true}"#).unwrap();
+        assert_eq!(
+            parsed,
+            TfidfConfig {
+                use_bigrams: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_idf_variants() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+
+        // df = 2 of N = 10: odds (10 - 2 + 0.5) / (2 + 0.5) = 3.4
+        assert!(close(IdfVariant::Lucene.idf(2.0, 10.0), 4.4f32.ln()));
+        assert!(close(
+            IdfVariant::RobertsonSparckJones.idf(2.0, 10.0),
+            3.4f32.ln()
+        ));
+        assert!(close(
+            IdfVariant::RobertsonSparckJonesClamped.idf(2.0, 10.0),
+            3.4f32.ln()
+        ));
+
+        // df = 8 of N = 10: odds 2.5 / 8.5
+        let odds = 2.5f32 / 8.5;
+        assert!(close(IdfVariant::Lucene.idf(8.0, 10.0), (1.0 + odds).ln()));
+        assert!(close(
+            IdfVariant::RobertsonSparckJones.idf(8.0, 10.0),
+            odds.ln()
+        ));
+        assert!(IdfVariant::RobertsonSparckJones.idf(8.0, 10.0) < 0.0);
+        assert_eq!(IdfVariant::RobertsonSparckJonesClamped.idf(8.0, 10.0), 0.0);
+
+        // The agent scores with the chosen variant
+        let data = [
+            example("rust ownership", ResponseFormat::Text("a".into())),
+            example("python typing", ResponseFormat::Text("b".into())),
+            example("go channels", ResponseFormat::Text("c".into())),
+        ];
+        let mut lucene = TfidfAgent::new();
+        lucene.train(&data);
+        let mut rsj = TfidfAgent::new().with_idf_variant(IdfVariant::RobertsonSparckJones);
+        rsj.train(&data);
+        let (lucene_score, rsj_score) = (lucene.rank("rust")[0].1, rsj.rank("rust")[0].1);
+        assert!(close(
+            lucene_score / rsj_score,
+            IdfVariant::Lucene.idf(1.0, 3.0) / IdfVariant::RobertsonSparckJones.idf(1.0, 3.0)
+        ));
+    }
+
+    #[test]
+    fn test_min_bm25_docs_fallback_for_tiny_corpus() {
+        let data = [
+            example("rust macros", ResponseFormat::Text("macros".into())),
+            example(
+                "rust ownership rules",
+                ResponseFormat::Text("ownership".into()),
+            ),
+        ];
+
+        // With two documents every IDF is zero, so nothing can be ranked
+        let mut plain = TfidfAgent::new()
+            .with_idf_variant(IdfVariant::RobertsonSparckJonesClamped)
+            .with_min_bm25_docs(0);
+        plain.train(&data);
+        assert!(plain.rank("rust ownership").is_empty());
+
+        // The fallback is on by default
+        let mut guarded =
+            TfidfAgent::new().with_idf_variant(IdfVariant::RobertsonSparckJonesClamped);
+        guarded.train(&data);
+        assert_eq!(guarded.predict_text("rust ownership"), "ownership");
+        assert_eq!(guarded.predict_text("macros in rust"), "macros");
+
+        let mut default_agent = TfidfAgent::new();
+        default_agent.train(&data);
+        assert_eq!(default_agent.config().min_bm25_docs, 3);
+        assert_eq!(
+            default_agent.rank("rust ownership"),
+            vec![(1, 2.0), (0, 1.0)]
+        );
+
+        // Once the corpus is large enough, BM25 is used again
+        let mut grown = TfidfAgent::new();
+        grown.train(&[
+            data[0].clone(),
+            data[1].clone(),
+            example("python typing", ResponseFormat::Text("typing".into())),
+        ]);
+        let mut reference = TfidfAgent::new().with_min_bm25_docs(0);
+        reference.train(grown.docs.as_slice());
+        assert_eq!(
+            grown.rank("rust ownership"),
+            reference.rank("rust ownership")
+        );
+    }
+
+    #[test]
+    fn test_weight_modes() {
+        let mut boosted = example(
+            "how do I bake bread",
+            ResponseFormat::Text("Bread recipe".into()),
+        );
+        boosted.weight = 50.0;
+        let data = [
+            example(
+                "how do I reset my password",
+                ResponseFormat::Text("Password reset".into()),
+            ),
+            boosted,
+            example("opening hours", ResponseFormat::Text("Hours".into())),
+        ];
+        let query = "how do I reset my password";
+
+        let mut agent = TfidfAgent::new();
+        agent.train(&data);
+        // The high weight makes a weak stopword match win
+        assert_eq!(String::from(agent.predict(query)), "Bread recipe");
+
+        for mode in [WeightMode::AdditivePrior, WeightMode::Ignore] {
+            let mut agent = TfidfAgent::new().with_weight_mode(mode);
+            agent.train(&data);
+            assert_eq!(String::from(agent.predict(query)), "Password reset");
+            // Weight never turns a non-match into a match
+            assert_eq!(agent.rank("opening").len(), 1);
+        }
+
+        // The prior still settles a tie between equally relevant examples
+        let mut heavy = example("reset password", ResponseFormat::Text("Heavy".into()));
+        heavy.weight = 2.0;
+        let tie = [
+            example("reset password", ResponseFormat::Text("Light".into())),
+            heavy,
+        ];
+        let mut agent = TfidfAgent::new().with_weight_mode(WeightMode::AdditivePrior);
+        agent.train(&tie);
+        assert_eq!(String::from(agent.predict("reset password")), "Heavy");
+        let mut agent = TfidfAgent::new().with_weight_mode(WeightMode::Ignore);
+        agent.train(&tie);
+        assert_eq!(
+            agent.rank("reset password")[0].1,
+            agent.rank("reset password")[1].1
+        );
+    }
+
+    #[test]
+    fn test_merge_index_equals_joint_training() {
+        let first = [
+            example(
+                "What is airust?",
+                ResponseFormat::Text("A Rust library".into()),
+            ),
+            example(
+                "What is BM25?",
+                ResponseFormat::Text("A ranking function".into()),
+            ),
+        ];
+        let second = [
+            example(
+                "How is BM25 tuned?",
+                ResponseFormat::Text("Via k1 and b".into()),
+            ),
+            example(
+                "Who wrote airust?",
+                ResponseFormat::Text("The maintainers".into()),
+            ),
+        ];
+
+        let mut merged = TfidfAgent::new();
+        merged.train(&first);
+        let mut shard = TfidfAgent::new();
+        shard.train(&second);
+        let shard_score = shard.rank("BM25")[0].1;
+        merged.merge_index(&shard).unwrap();
+
+        let mut joint = TfidfAgent::new();
+        joint.train(&[first.as_slice(), second.as_slice()].concat());
+
+        let inputs = |agent: &TfidfAgent| -> Vec<String> {
+            agent.docs.iter().map(|doc| doc.input.clone()).collect()
+        };
+        assert_eq!(inputs(&merged), inputs(&joint));
+        assert_eq!(merged.doc_term_freq, joint.doc_term_freq);
+        assert_eq!(merged.term_df, joint.term_df);
+        assert_eq!(merged.doc_count, joint.doc_count);
+        for query in ["what is airust", "BM25", "who wrote it"] {
+            assert_eq!(merged.rank(query), joint.rank(query));
+        }
+        // IDF changed with the larger corpus
+        assert_ne!(merged.rank("BM25")[0].1, shard_score);
+
+        let bigrams = TfidfAgent::new().with_bigrams(true);
+        assert!(merged.merge_index(&bigrams).is_err());
+    }
+
+    #[test]
+    fn test_skip_empty_outputs() {
+        let data = [
+            example("reset password steps", ResponseFormat::Text("  \n ".into())),
+            example(
+                "reset password",
+                ResponseFormat::Text("Use the reset link".into()),
+            ),
+            example("opening hours", ResponseFormat::Text("".into())),
+        ];
+        let query = "reset password steps";
+
+        let mut agent = TfidfAgent::new();
+        agent.train(&data);
+        assert_eq!(String::from(agent.predict(query)).trim(), "");
+
+        let mut agent = TfidfAgent::new().with_skip_empty_outputs(true);
+        agent.train(&data);
+        assert_eq!(String::from(agent.predict(query)), "Use the reset link");
+        // Without a non-empty match there is no answer
+        assert_eq!(
+            String::from(agent.predict("opening hours")),
+            "No matching answer found."
+        );
+    }
+
+    #[test]
+    fn test_min_term_matches() {
+        let data = [
+            example(
+                "printer setup guide",
+                ResponseFormat::Text("Printer".into()),
+            ),
+            example(
+                "reset your router password",
+                ResponseFormat::Text("Router".into()),
+            ),
+        ];
+        let query = "how to reset printer password";
+
+        let mut agent = TfidfAgent::new();
+        agent.train(&data);
+        assert_eq!(agent.rank(query).len(), 2);
+
+        let mut agent = TfidfAgent::new().with_min_term_matches(2);
+        agent.train(&data);
+        // "printer" alone is incidental; "reset" and "password" both match the router entry
+        assert_eq!(agent.rank(query).len(), 1);
+        assert_eq!(String::from(agent.predict(query)), "Router");
+        assert!(agent.rank("printer").is_empty());
+    }
+
+    #[test]
+    fn test_predict_with_context_tag() {
+        let mut install = example(
+            "How do I install the tool?",
+            ResponseFormat::Text("Download the installer".into()),
+        );
+        install.metadata = Some(serde_json::json!({
+            "variants": {
+                "linux": "Run apt install tool",
+                "macos": {"Markdown": "Run `brew install tool`"}
+            }
+        }));
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            install,
+            example("What does it cost?", ResponseFormat::Text("Nothing".into())),
+        ]);
+
+        let query = "install the tool";
+        assert_eq!(
+            String::from(agent.predict_with_context_tag(query, "linux")),
+            "Run apt install tool"
+        );
+        assert!(matches!(
+            agent.predict_with_context_tag(query, "macos"),
+            ResponseFormat::Markdown(md) if md == "Run `brew install tool`"
+        ));
+        // Unknown tags and examples without variants use the default output
+        assert_eq!(
+            String::from(agent.predict_with_context_tag(query, "windows")),
+            "Download the installer"
+        );
+        assert_eq!(
+            String::from(agent.predict_with_context_tag("what does it cost", "linux")),
+            "Nothing"
+        );
+        assert_eq!(
+            String::from(agent.predict_with_context_tag("unrelated", "linux")),
+            "No matching answer found."
+        );
+    }
+
+    #[test]
+    fn test_confidence_threshold_controls_can_answer() {
+        let data = [
+            example("rust ownership rules", ResponseFormat::Text("a".into())),
+            example("go goroutines", ResponseFormat::Text("b".into())),
+        ];
+        let query = "rust ownership rules";
+
+        let mut lenient = TfidfAgent::new().with_confidence_threshold(0.3);
+        lenient.train(&data);
+        let confidence = lenient.confidence(query);
+        assert!(confidence > 0.3 && confidence < 0.9, "{}", confidence);
+        assert!(lenient.can_answer(query));
+
+        let mut strict = TfidfAgent::new().with_confidence_threshold(0.9);
+        strict.train(&data);
+        assert!(!strict.can_answer(query));
+
+        // Indices saved without the field use the default
+        let parsed: TfidfConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(parsed.confidence_threshold, DEFAULT_CONFIDENCE_THRESHOLD);
+    }
+
+    #[cfg(feature = "timing")]
+    #[test]
+    fn test_last_timing_is_recorded() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[example(
+            "rust ownership rules",
+            ResponseFormat::Text("a".into()),
+        )]);
+        assert!(agent.last_timing().is_none());
+
+        agent.predict("rust ownership");
+        let timing = agent.last_timing().unwrap();
+        assert!(timing.tokenize + timing.score + timing.sort > std::time::Duration::ZERO);
+
+        // A query that never reaches scoring must not report the previous durations
+        agent.doc_count = 0.0;
+        agent.predict("rust ownership");
+        assert!(agent.last_timing().is_none());
+    }
+
+    #[cfg(feature = "timing")]
+    #[test]
+    fn test_agent_is_sync_with_timing() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<TfidfAgent>();
+    }
+
+    #[test]
+    fn test_empty_query() {
+        let mut agent = TfidfAgent::new();
+        assert_eq!(agent.predict_text(""), "No training data available.");
+
+        agent.train(&[example("rust ownership", ResponseFormat::Text("a".into()))]);
+        for query in ["", " \t "] {
+            assert_eq!(agent.predict_text(query), EMPTY_QUERY_RESPONSE);
+            assert_eq!(agent.confidence(query), 0.0);
+        }
+
+        // Every entry point treats blank input alike, even if preprocessing adds terms
+        let mut agent =
+            TfidfAgent::new().with_input_preprocessor(Box::new(|text| format!("{} rust", text)));
+        agent.train(&[
+            example("rust ownership", ResponseFormat::Text("a".into())),
+            example("rust traits", ResponseFormat::Text("b".into())),
+        ]);
+        assert_eq!(agent.predict_text("ownership"), "a");
+
+        let blank = " \t ";
+        let empty = ResponseFormat::Text(EMPTY_QUERY_RESPONSE.to_string());
+        assert_eq!(agent.predict(blank), empty);
+        assert_eq!(agent.confidence(blank), 0.0);
+        assert_eq!(agent.query_coverage(blank), 0.0);
+        assert_eq!(agent.predict_ref(blank), None);
+        assert_eq!(agent.predict_snippet(blank, 1), None);
+        assert!(agent.predict_above(blank, 0.0).is_empty());
+        assert!(agent.predict_top_n(blank, 2).is_empty());
+        assert!(agent.predict_distinct(blank, 2).is_empty());
+        assert_eq!(agent.predict_or_clarify(blank, 1.0), empty);
+        assert_eq!(agent.predict_with_context_tag(blank, "short"), empty);
+        assert_eq!(agent.predict_within(blank, |_| true), empty);
+        assert_eq!(agent.predict_merged(blank, 2), empty);
+    }
+
+    #[test]
+    fn test_max_input_chars_truncates_queries() {
+        let mut agent = TfidfAgent::new().with_max_input_chars(14);
+        agent.train(&[
+            example("rust ownership", ResponseFormat::Text("borrowing".into())),
+            example("python typing", ResponseFormat::Text("hints".into())),
+        ]);
+
+        // Only "rust ownership" is scored, the pasted tail about python is cut off
+        let result = agent.predict_with_metadata("rust ownership python typing python typing");
+        assert_eq!(String::from(result.response), "borrowing");
+        assert_eq!(
+            result.metadata.unwrap()[crate::agent::INPUT_TRUNCATED_KEY],
+            true
+        );
+
+        let result = agent.predict_with_metadata("python typing");
+        assert_eq!(String::from(result.response), "hints");
+        assert!(result.metadata.is_none());
+    }
+
+    #[test]
+    fn test_predict_snippet() {
+        let chunk = "Rust was started at Mozilla. It has no garbage collector. \
+                     Ownership rules free memory deterministically! Lifetimes track borrows. \
+                     Cargo builds the code.";
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example(chunk, ResponseFormat::Text(chunk.into())),
+            example("python typing", ResponseFormat::Text("hints".into())),
+        ]);
+
+        assert_eq!(
+            agent.predict_snippet("ownership memory", 0).unwrap(),
+            "Ownership rules free memory deterministically!"
+        );
+        assert_eq!(
+            agent.predict_snippet("ownership memory", 1).unwrap(),
+            "It has no garbage collector. Ownership rules free memory deterministically! \
+             Lifetimes track borrows."
+        );
+        // The window stops at the ends of the answer
+        assert_eq!(
+            agent.predict_snippet("mozilla", 1).unwrap(),
+            "Rust was started at Mozilla. It has no garbage collector."
+        );
+        assert_eq!(agent.predict_snippet("python typing", 2).unwrap(), "hints");
+        assert!(agent.predict_snippet("haskell", 1).is_none());
+    }
+
+    #[test]
+    fn test_invalid_weights_do_not_break_ranking() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example("rust ownership", ResponseFormat::Text("a".into())),
+            TrainingExample {
+                input: "rust ownership borrowing".to_string(),
+                output: ResponseFormat::Text("nan".into()),
+                weight: f32::NAN,
+                metadata: None,
+            },
+            TrainingExample {
+                input: "rust ownership lifetimes".to_string(),
+                output: ResponseFormat::Text("negative".into()),
+                weight: -3.0,
+                metadata: None,
+            },
+            example(
+                "rust ownership rules explained",
+                ResponseFormat::Text("b".into()),
+            ),
+        ]);
+
+        assert_eq!(agent.docs[1].weight, 0.0);
+        assert_eq!(agent.docs[2].weight, 0.0);
+        let ranked = agent.rank("rust ownership");
+        assert_eq!(
+            ranked.iter().map(|&(idx, _)| idx).collect::<Vec<_>>(),
+            vec![0, 3]
+        );
+        assert!(ranked.iter().all(|&(_, score)| score.is_finite()));
+        assert_eq!(agent.predict_text("rust ownership borrowing"), "a");
+
+        // The other modes keep zero-weight documents ranked by relevance alone
+        for mode in [WeightMode::AdditivePrior, WeightMode::Ignore] {
+            agent.weight_mode = mode;
+            let ranked = agent.rank("rust ownership");
+            assert_eq!(ranked.len(), 4, "{:?}", mode);
+            assert!(ranked
+                .iter()
+                .all(|&(_, score)| score.is_finite() && score > 0.0));
+            assert_eq!(agent.predict_text("rust ownership borrowing"), "nan");
+        }
+    }
+
+    #[test]
+    fn test_query_coverage() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example("rust ownership rules", ResponseFormat::Text("a".into())),
+            example("rust borrow checker", ResponseFormat::Text("b".into())),
+        ]);
+
+        assert_eq!(agent.query_coverage("Rust ownership"), 1.0);
+        // Repeated terms count once
+        assert_eq!(agent.query_coverage("rust rust borrow weather"), 2.0 / 3.0);
+        assert_eq!(agent.query_coverage("python weather forecast"), 0.0);
+        assert_eq!(agent.query_coverage("?!"), 0.0);
+    }
+
+    #[test]
+    fn test_predict_ref_matches_predict() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example(
+                "What is airust?",
+                ResponseFormat::Text("A Rust library".into()),
+            ),
+            example(
+                "Show the config",
+                ResponseFormat::Json(serde_json::json!({"k1": 1.2, "b": 0.75})),
+            ),
+        ]);
+
+        for query in ["what is airust", "config"] {
+            let borrowed = agent.predict_ref(query).unwrap();
+            assert_eq!(borrowed.to_string(), agent.predict(query).to_string());
+        }
+        assert!(agent.predict_ref("unrelated").is_none());
+        assert!(TfidfAgent::new().predict_ref("anything").is_none());
+    }
+
+    #[test]
+    fn test_known_inputs() {
+        let data = [
+            example(
+                "What is airust?",
+                ResponseFormat::Text("A Rust library".into()),
+            ),
+            example(
+                "What is BM25?",
+                ResponseFormat::Text("A ranking function".into()),
+            ),
+        ];
+        let mut agent = TfidfAgent::new();
+        agent.train(&data);
+        assert_eq!(
+            agent.known_inputs(),
+            vec!["What is airust?", "What is BM25?"]
+        );
+
+        // Wrappers report the inputs of the agent they wrap
+        let context = ContextAgent::new(agent, 2);
+        assert_eq!(context.known_inputs().len(), 2);
+    }
+
+    #[test]
+    fn test_predict_or_clarify() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example(
+                "how do I reset my password",
+                ResponseFormat::Text("Use the reset link".into()),
+            ),
+            example(
+                "how do I change my email",
+                ResponseFormat::Text("Open the settings".into()),
+            ),
+            example(
+                "how do I reset my router",
+                ResponseFormat::Text("Hold the button".into()),
+            ),
+        ]);
+
+        // "reset" matches two questions equally well
+        let clarification = String::from(agent.predict_or_clarify("reset", 0.5));
+        assert!(clarification.starts_with("Did you mean one of the following?"));
+        assert!(clarification.contains("- how do I reset my password"));
+        assert!(clarification.contains("- how do I reset my router"));
+        assert!(!clarification.contains("email"));
+
+        // A dominant match is answered directly
+        assert_eq!(
+            String::from(agent.predict_or_clarify("reset password", 0.5)),
+            "Use the reset link"
+        );
+        assert_eq!(
+            String::from(agent.predict_or_clarify("email", 0.5)),
+            "Open the settings"
+        );
+
+        // A margin of zero never asks
+        assert!(!String::from(agent.predict_or_clarify("reset", 0.0)).starts_with("Did you"));
+    }
+
+    #[test]
+    fn test_index_size() {
+        let mut agent = TfidfAgent::new();
+        assert_eq!(agent.index_size().approx_bytes, 0);
+
+        agent.train(&[
+            example("rust memory safety", ResponseFormat::Text("a".into())),
+            example("rust concurrency", ResponseFormat::Text("b".into())),
+            example("python typing typing", ResponseFormat::Text("c".into())),
+        ]);
+
+        let stats = agent.index_size();
+        assert_eq!(stats.documents, 3);
+        // rust, memory, safety, concurrency, python, typing
+        assert_eq!(stats.unique_terms, 6);
+        assert_eq!(stats.total_postings, 3 + 2 + 2);
+        assert!(stats.approx_bytes > "rustmemorysafetyconcurrencypythontyping".len());
+
+        agent.train(&[example("rust", ResponseFormat::Text("a".into()))]);
+        assert!(agent.index_size().approx_bytes < stats.approx_bytes);
+    }
+
+    #[test]
+    fn test_bm25_plus_favors_long_relevant_document() {
+        let filler = "the guide covers installation setup testing tooling and deployment \
+                      in great detail with many examples and explanations for every step";
+        let long = format!("rust compiler errors explained {}", filler);
+        let docs = [
+            example(&long, ResponseFormat::Text("long".into())),
+            example("rust tips", ResponseFormat::Text("short".into())),
+            example("compiler design", ResponseFormat::Text("other".into())),
+            example("python basics", ResponseFormat::Text("other".into())),
+        ];
+        let best = |agent: TfidfAgent| {
+            let mut agent = agent;
+            agent.train(&docs);
+            String::from(agent.predict("rust compiler"))
+        };
+
+        assert_eq!(best(TfidfAgent::new().with_bm25_params(1.2, 1.0)), "short");
+        assert_eq!(
+            best(TfidfAgent::new().with_bm25_plus(1.2, 1.0, 1.0)),
+            "long"
+        );
+
+        // A delta of zero reproduces plain BM25
+        let mut plain = TfidfAgent::new();
+        let mut plus = TfidfAgent::new().with_bm25_plus(1.2, 0.75, 0.0);
+        plain.train(&docs);
+        plus.train(&docs);
+        assert_eq!(
+            plain.predict_above("rust compiler", 0.0)[0].confidence,
+            plus.predict_above("rust compiler", 0.0)[0].confidence
+        );
+    }
+
+    #[test]
+    fn test_input_preprocessor_changes_match() {
+        let docs = [
+            example("contact sales", ResponseFormat::Text("sales".into())),
+            example("support", ResponseFormat::Text("support".into())),
+        ];
+        let query = "**[contact support](https://example.com)**";
+
+        // Punctuation is dropped without splitting, so "support" merges with the link target
+        let mut plain = TfidfAgent::new().with_min_bm25_docs(0);
+        plain.train(&docs);
+        assert_eq!(
+            plain.preprocess(query),
+            vec!["contact", "supporthttpsexamplecom"]
+        );
+        assert_eq!(String::from(plain.predict(query)), "sales");
+
+        // Strips bold markers and link targets from documents and queries
+        let strip_markdown: InputPreprocessor = Box::new(|text: &str| {
+            let link_target = regex::Regex::new(r"\]\([^)]*\)").unwrap();
+            link_target
+                .replace_all(&text.replace("**", ""), "]")
+                .to_string()
+        });
+        let mut agent = TfidfAgent::new()
+            .with_min_bm25_docs(0)
+            .with_input_preprocessor(strip_markdown);
+        agent.train(&docs);
+        assert_eq!(agent.preprocess(query), vec!["contact", "support"]);
+        assert_eq!(String::from(agent.predict(query)), "support");
+    }
+
+    #[test]
+    fn test_top_terms() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example(
+                "rust ownership borrowing ownership",
+                ResponseFormat::Text("a".into()),
+            ),
+            example("rust cargo crates", ResponseFormat::Text("b".into())),
+            example("python pip packages", ResponseFormat::Text("c".into())),
+        ]);
+
+        let top = agent.top_terms(0, 2);
+        let terms: Vec<&str> = top.iter().map(|(term, _)| term.as_str()).collect();
+        // "rust" also occurs in another document and ranks below the distinctive terms
+        assert_eq!(terms, vec!["ownership", "borrowing"]);
+        assert!(top[0].1 >= top[1].1);
+
+        assert_eq!(agent.top_terms(0, 10).len(), 3);
+        assert!(agent.top_terms(99, 3).is_empty());
+    }
+
+    #[test]
+    fn test_predict_merged() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example(
+                "rust memory safety",
+                ResponseFormat::Text("Ownership".into()),
+            ),
+            example(
+                "rust concurrency",
+                ResponseFormat::Text("Send and Sync".into()),
+            ),
+            example("python typing", ResponseFormat::Text("Type hints".into())),
+        ]);
+
+        let merged = String::from(agent.predict_merged("rust", 2));
+        assert!(merged.contains("Ownership"));
+        assert!(merged.contains("Send and Sync"));
+        assert!(!merged.contains("Type hints"));
+
+        let single = String::from(agent.predict_merged("rust safety", 1));
+        assert_eq!(single, "Ownership");
+
+        let none = String::from(agent.predict_merged("haskell", 3));
+        assert_eq!(none, "No matching answer found.");
+    }
+
+    #[test]
+    fn test_predict_above() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example("rust ownership borrowing", ResponseFormat::Text("a".into())),
+            example("rust traits", ResponseFormat::Text("b".into())),
+            example("python decorators", ResponseFormat::Text("c".into())),
+        ]);
+
+        let all = agent.predict_above("rust ownership", 0.0);
+        assert_eq!(all.len(), 2);
+        assert!(all[0].confidence >= all[1].confidence);
+        assert_eq!(all[0].metadata.as_ref().unwrap()["example_index"], 0);
+        assert_eq!(all[1].metadata.as_ref().unwrap()["example_index"], 1);
+
+        // Raising the cutoff between both scores keeps only the best document
+        let cutoff = (all[0].confidence + all[1].confidence) / 2.0;
+        let best = agent.predict_above("rust ownership", cutoff);
+        assert_eq!(best.len(), 1);
+        assert_eq!(String::from(best[0].response.clone()), "a");
+
+        assert!(agent
+            .predict_above("rust ownership", all[0].confidence + 1.0)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_bigrams_reward_phrase_matches() {
+        let data = [
+            example(
+                "an introduction to machine learning for beginners",
+                ResponseFormat::Text("phrase".into()),
+            ),
+            example(
+                "learning the machine",
+                ResponseFormat::Text("scattered".into()),
+            ),
+        ];
+
+        let mut unigram = TfidfAgent::new().with_min_bm25_docs(0);
+        unigram.train(&data);
+        assert_eq!(unigram.predict_text("machine learning"), "scattered");
+
+        let mut bigram = TfidfAgent::new().with_min_bm25_docs(0).with_bigrams(true);
+        bigram.train(&data);
+        assert_eq!(bigram.predict_text("machine learning"), "phrase");
+    }
+
+    #[test]
+    fn test_predict_within_scope() {
+        let tagged = |input: &str, output: &str, section: &str| TrainingExample {
+            metadata: Some(serde_json::json!({ "section": section })),
+            ..example(input, ResponseFormat::Text(output.into()))
+        };
+
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            tagged("how do I reset my password", "Account reset", "account"),
+            tagged("password on invoices", "Billing password", "billing"),
+            example("password policy", ResponseFormat::Text("Untagged".into())),
+        ]);
+
+        let billing = |m: &serde_json::Value| m["section"] == "billing";
+        assert_eq!(agent.predict_text("reset password"), "Account reset");
+        assert_eq!(
+            String::from(agent.predict_within("reset password", billing)),
+            "Billing password"
+        );
+        assert_eq!(
+            String::from(agent.predict_within("reset password", |m| m["section"] == "hr")),
+            "No matching answer found."
+        );
+    }
+
+    #[test]
+    fn test_case_sensitive_terms() {
+        let data = [
+            example("NASA missions", ResponseFormat::Text("space agency".into())),
+            example(
+                "tell nasa about us",
+                ResponseFormat::Text("lowercase".into()),
+            ),
+        ];
+
+        let mut agent = TfidfAgent::new().with_case_sensitive(true);
+        agent.train(&data);
+        assert_eq!(agent.predict_text("NASA"), "space agency");
+        assert_eq!(agent.predict_text("nasa"), "lowercase");
+
+        let mut agent = TfidfAgent::new();
+        agent.train(&data);
+        assert_eq!(agent.term_df.get("nasa"), Some(&2.0));
+    }
+
+    #[test]
+    fn test_min_answer_length_prefers_substantive_answers() {
+        let data = [
+            example(
+                "is the service available",
+                ResponseFormat::Text("Yes".into()),
+            ),
+            example(
+                "is the service available",
+                ResponseFormat::Text("Yes, the service runs around the clock".into()),
+            ),
+        ];
+
+        let mut agent = TfidfAgent::new();
+        agent.train(&data);
+        assert_eq!(agent.predict_text("service available"), "Yes");
+
+        let mut agent = TfidfAgent::new().with_min_answer_length(5);
+        agent.train(&data);
+        assert_eq!(
+            agent.predict_text("service available"),
+            "Yes, the service runs around the clock"
+        );
+
+        let ranked = agent.predict_above("service available", 0.0);
+        assert!(ranked[1].confidence < ranked[0].confidence / 2.0);
+    }
+
+    #[test]
+    fn test_stable_ordering_gives_identical_indices() {
+        let data = vec![
+            example("rust ownership", ResponseFormat::Text("a".into())),
+            example("python typing", ResponseFormat::Text("b".into())),
+            example("go channels", ResponseFormat::Text("c".into())),
+            example("rust ownership", ResponseFormat::Text("d".into())),
+        ];
+        let shuffled = vec![
+            data[2].clone(),
+            data[3].clone(),
+            data[0].clone(),
+            data[1].clone(),
+        ];
+
+        let mut first = TfidfAgent::new().with_stable_ordering(true);
+        first.train(&data);
+        let mut second = TfidfAgent::new().with_stable_ordering(true);
+        second.train(&shuffled);
+        assert_eq!(
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+
+        let mut unordered = TfidfAgent::new();
+        unordered.train(&shuffled);
+        assert_ne!(
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&unordered).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_retrieval_snippets() {
+        let chunk = "Rust guarantees memory safety through ownership and borrowing rules.";
+        let data = [
+            example(chunk, ResponseFormat::Text(chunk.into())),
+            example(
+                "what is cargo",
+                ResponseFormat::Text("Cargo is the Rust package manager and build tool.".into()),
+            ),
+        ];
+
+        let mut agent = TfidfAgent::new().with_retrieval_snippets(20);
+        agent.train(&data);
+
+        let snippet = agent.predict_text("memory safety ownership");
+        assert_eq!(snippet.chars().count(), 20);
+        assert!(snippet.starts_with("Rust guarantees"));
+
+        // Question/answer examples are returned in full
+        assert_eq!(
+            agent.predict_text("cargo"),
+            "Cargo is the Rust package manager and build tool."
+        );
+
+        let mut full = TfidfAgent::new();
+        full.train(&data);
+        assert_eq!(full.predict_text("memory safety ownership"), chunk);
+    }
+
+    #[test]
+    fn test_stopword_only_match_has_low_confidence() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example("the cat and the dog", ResponseFormat::Text("Pets".into())),
+            example("birds fly south", ResponseFormat::Text("Migration".into())),
+        ]);
+
+        // The query matches through stopwords only
+        assert_eq!(agent.predict_text("the and"), "Pets");
+        assert!(agent.confidence("the and") <= STOPWORD_ONLY_CONFIDENCE);
+
+        assert!(agent.confidence("the cat") > STOPWORD_ONLY_CONFIDENCE);
+        assert_eq!(agent.confidence("fish"), 0.0);
+
+        // Bigrams of stopwords ("and the") do not lift the confidence either
+        let mut agent = TfidfAgent::new().with_bigrams(true);
+        agent.train(&[
+            example("the cat and the dog", ResponseFormat::Text("Pets".into())),
+            example("birds fly south", ResponseFormat::Text("Migration".into())),
+        ]);
+        assert!(agent.preprocess("and the").contains(&"and the".to_string()));
+        assert_eq!(agent.predict_text("and the"), "Pets");
+        assert!(agent.confidence("and the") <= STOPWORD_ONLY_CONFIDENCE);
+        assert!(agent.confidence("the cat") > STOPWORD_ONLY_CONFIDENCE);
+    }
+
+    #[test]
+    fn test_apply_diff_matches_fresh_training() {
+        let corpus = vec![
+            example("rust ownership rules", ResponseFormat::Text("a".into())),
+            example(
+                "python garbage collection",
+                ResponseFormat::Text("b".into()),
+            ),
+            example("rust borrow checker", ResponseFormat::Text("c".into())),
+        ];
+        let added = vec![example("go goroutines", ResponseFormat::Text("d".into()))];
+
+        let mut incremental = TfidfAgent::new();
+        incremental.train(&corpus);
+        incremental.apply_diff(&added, &[1]);
+
+        let mut fresh = TfidfAgent::new();
+        fresh.train(&[corpus[0].clone(), corpus[2].clone(), added[0].clone()]);
+
+        let inputs = |agent: &TfidfAgent| -> Vec<String> {
+            agent.docs.iter().map(|d| d.input.clone()).collect()
+        };
+        assert_eq!(inputs(&incremental), inputs(&fresh));
+        assert_eq!(incremental.doc_count, fresh.doc_count);
+        assert_eq!(incremental.term_df, fresh.term_df);
+        assert_eq!(incremental.doc_term_freq, fresh.doc_term_freq);
+        assert!(!incremental.term_df.contains_key("python"));
+        assert_eq!(incremental.predict_text("goroutines"), "d");
+    }
+
+    #[test]
+    fn test_recompute_statistics_matches_fresh_training() {
+        let corpus = vec![
+            example("rust ownership rules", ResponseFormat::Text("a".into())),
+            example("rust borrow checker", ResponseFormat::Text("b".into())),
+            example("go goroutines", ResponseFormat::Text("c".into())),
+        ];
+        let mut agent = TfidfAgent::new();
+        agent.train(&corpus);
+        agent.apply_diff(
+            &[example("rust macros", ResponseFormat::Text("d".into()))],
+            &[0],
+        );
+        agent.apply_diff(
+            &[example("go channels", ResponseFormat::Text("e".into()))],
+            &[],
+        );
+
+        // Consistent statistics are left untouched
+        assert!(!agent.recompute_statistics());
+
+        // Simulate drifted statistics
+        agent.term_df.insert("rust".to_string(), 7.0);
+        agent.term_df.swap_remove("go");
+        agent.term_df.insert("stale".to_string(), 1.0);
+        agent.doc_count = 2.0;
+        assert!(agent.recompute_statistics());
+
+        let docs = agent.docs.clone();
+        let mut fresh = TfidfAgent::new();
+        fresh.train(&docs);
+        assert_eq!(agent.term_df, fresh.term_df);
+        assert_eq!(agent.doc_count, fresh.doc_count);
+        assert_eq!(agent.doc_term_freq, fresh.doc_term_freq);
+        for query in ["rust", "go channels", "macros"] {
+            assert_eq!(agent.rank(query), fresh.rank(query));
+        }
+    }
+
+    #[test]
+    fn test_scoring_functions() {
+        let mut agent = TfidfAgent::new();
+        agent.train(&[
+            example("apple banana", ResponseFormat::Text("a".into())),
+            example("apple apple apple cherry", ResponseFormat::Text("b".into())),
+            example("banana cherry date", ResponseFormat::Text("c".into())),
+        ]);
+
+        let order = |agent: &TfidfAgent, query: &str| -> Vec<usize> {
+            agent.rank(query).into_iter().map(|(idx, _)| idx).collect()
+        };
+
+        // BM25 (default) rewards the repeated term
+        assert_eq!(order(&agent, "apple"), vec![1, 0]);
+
+        // Switching keeps the trained index
+        let agent = agent.with_scoring(ScoringFn::CountOverlap);
+        assert_eq!(order(&agent, "apple"), vec![0, 1]);
+        assert_eq!(order(&agent, "banana cherry date"), vec![2, 0, 1]);
+
+        let agent = agent.with_scoring(ScoringFn::TfIdfCosine);
+        assert_eq!(order(&agent, "apple"), vec![1, 0]);
+        assert_eq!(order(&agent, "banana date")[0], 2);
+
+        let agent = agent.with_bm25_params(1.2, 0.75);
+        assert_eq!(agent.scoring, ScoringFn::default());
+    }
+
+    #[test]
+    fn test_inconsistent_index_degrades_gracefully() {
+        let data = [
+            example("rust ownership", ResponseFormat::Text("a".into())),
+            example("rust traits", ResponseFormat::Text("b".into())),
+        ];
+
+        let mut agent = TfidfAgent::new();
+        agent.train(&data);
+        agent.doc_term_freq.pop();
+        assert_eq!(agent.predict_text("rust"), "No matching answer found.");
+        assert_eq!(agent.confidence("rust"), 0.0);
+
+        let mut agent = TfidfAgent::new();
+        agent.train(&data);
+        agent.doc_count = 0.0;
+        assert_eq!(agent.predict_text("rust"), "No matching answer found.");
+        assert_eq!(
+            String::from(agent.predict_merged("rust", 2)),
+            "No matching answer found."
+        );
+    }
+
+    #[test]
+    fn test_save_and_load_index_version() {
+        let path = std::env::temp_dir().join(format!("airust_{}_index.json", std::process::id()));
+
+        let mut agent = TfidfAgent::new();
+        agent.train(&[example(
+            "rust ownership",
+            ResponseFormat::Text("Borrowing".into()),
+        )]);
+        agent.save(&path).unwrap();
+
+        let loaded = TfidfAgent::load_strict(&path).unwrap();
+        assert_eq!(loaded.predict_text("ownership"), "Borrowing");
+
+        // Rewrite the stored version to simulate an index from another release
+        let mut raw: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        raw["version"] = serde_json::json!("0.0.0");
+        fs::write(&path, raw.to_string()).unwrap();
+
+        let err = TfidfAgent::load_strict(&path).err().unwrap();
+        assert!(err.contains("0.0.0"));
+        assert!(TfidfAgent::load(&path).is_ok());
+
+        fs::remove_file(path).ok();
+    }
+}
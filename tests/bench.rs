@@ -0,0 +1,75 @@
+// tests/bench.rs - Integration tests for the bench binary
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Creates a working directory with a tiny knowledge base and query file
+fn fixture(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("airust_bench_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    fs::write(
+        dir.join("kb.json"),
+        r#"[
+            {"input": "What is airust?", "output": {"Text": "A Rust library"}},
+            {"input": "What is BM25?", "output": {"Text": "A ranking function"}}
+        ]"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("queries.txt"),
+        "What is airust?\n\nranking function\n",
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn test_bench_prints_timing_summary() {
+    let dir = fixture("summary");
+
+    for agent in ["simple", "fuzzy", "tfidf", "context"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+            .args([
+                "kb.json",
+                "queries.txt",
+                "--agent",
+                agent,
+                "--iterations",
+                "3",
+            ])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains(&format!("Agent: {}", agent)));
+        assert!(stdout.contains("Queries: 2 x 3 iterations"));
+        for line in [
+            "Total time:",
+            "Mean latency:",
+            "p95 latency:",
+            "Throughput:",
+        ] {
+            assert!(stdout.contains(line), "missing '{}' in {}", line, stdout);
+        }
+    }
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_bench_rejects_unknown_agent() {
+    let dir = fixture("unknown_agent");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+        .args(["kb.json", "queries.txt", "--agent", "oracle"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,390 @@
+// tests/cli.rs - Integration tests for the airust CLI
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Creates an empty working directory with a knowledge/ folder
+fn temp_workdir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("airust_cli_{}_{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("knowledge")).unwrap();
+    dir
+}
+
+#[test]
+fn test_knowledge_add_appends_example() {
+    let dir = temp_workdir("knowledge_add");
+    let train = dir.join("knowledge").join("train.json");
+    fs::write(
+        &train,
+        r#"[{"input": "What is airust?", "output": {"Text": "A Rust library"}, "weight": 1.0}]"#,
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_airust"))
+        .args([
+            "knowledge",
+            "add",
+            "--input",
+            "What is BM25?",
+            "--output",
+            "A ranking function",
+            "--weight",
+            "2.5",
+        ])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&train).unwrap()).unwrap();
+    let examples = saved["examples"].as_array().unwrap();
+    assert_eq!(examples.len(), 2);
+    assert_eq!(examples[1]["input"], "What is BM25?");
+    assert_eq!(examples[1]["output"]["Text"], "A ranking function");
+    assert_eq!(examples[1]["weight"], 2.5);
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_knowledge_add_requires_existing_file() {
+    let dir = temp_workdir("knowledge_add_missing");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_airust"))
+        .args(["knowledge", "add", "--input", "q", "--output", "a"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_knowledge_create_undo_and_edit() {
+    let dir = temp_workdir("knowledge_create");
+    let kb_path = dir.join("created.json");
+
+    let script = [
+        // Create a new knowledge base
+        "1",
+        // Add an example with a typo and remove it again
+        "Typo questoin",
+        "Wrong answer",
+        "",
+        "undo",
+        // Add two examples and correct the second one
+        "What is Rust?",
+        "A language",
+        "2",
+        "list",
+        "Second",
+        "Answer",
+        "",
+        "edit 1",
+        "What is Cargo?",
+        "The package manager",
+        "",
+        // Out of range, ignored
+        "edit 7",
+        // Finish, save and skip testing
+        "",
+        kb_path.to_str().unwrap(),
+        "n",
+    ]
+    .join("\n");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_airust"))
+        .arg("knowledge")
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(format!("{}\n", script).as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&kb_path).unwrap()).unwrap();
+    let examples = saved["examples"].as_array().unwrap();
+    assert_eq!(examples.len(), 2);
+    assert_eq!(examples[0]["input"], "What is Rust?");
+    assert_eq!(examples[0]["weight"], 2.0);
+    assert_eq!(examples[1]["input"], "What is Cargo?");
+    assert_eq!(examples[1]["output"]["Text"], "The package manager");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Removed: Typo questoin"));
+    assert!(stdout.contains("0. What is Rust? -> A language (weight 2)"));
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_knowledge_create_stores_json_answer() {
+    let dir = temp_workdir("knowledge_create_json");
+    let kb_path = dir.join("created.json");
+
+    let script = [
+        "1",
+        // Confirmed JSON answer
+        "Server status?",
+        r#"{"status": "ok", "uptime": 42}"#,
+        "y",
+        "",
+        // Declined JSON answer stays text
+        "Raw list?",
+        "[1, 2]",
+        "n",
+        "",
+        "",
+        kb_path.to_str().unwrap(),
+        "n",
+    ];
+    let stdout = run_with_stdin(&dir, &["knowledge"], &script);
+    assert!(stdout.contains("Store it as JSON?"));
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&kb_path).unwrap()).unwrap();
+    let examples = saved["examples"].as_array().unwrap();
+    assert_eq!(
+        examples[0]["output"]["Json"],
+        serde_json::json!({"status": "ok", "uptime": 42})
+    );
+    assert_eq!(examples[1]["output"]["Text"], "[1, 2]");
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_knowledge_test_loop_suggests_close_question() {
+    let dir = temp_workdir("did_you_mean");
+    let kb_path = dir.join("kb.json");
+    fs::write(
+        &kb_path,
+        r#"[{"input": "What is airust?", "output": {"Text": "A Rust library"}}]"#,
+    )
+    .unwrap();
+
+    let script = [
+        // Load the knowledge base and test it with the exact agent
+        "2",
+        kb_path.to_str().unwrap(),
+        "1",
+        "1",
+        // A near miss and an unrelated question
+        "What is airst",
+        "How is the weather today",
+        "exit",
+    ]
+    .join("\n");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_airust"))
+        .arg("knowledge")
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(format!("{}\n", script).as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("): No matching answer found."),
+        "{}",
+        stdout
+    );
+    assert_eq!(stdout.matches("Did you mean: What is airust?").count(), 1);
+
+    fs::remove_dir_all(dir).ok();
+}
+
+/// Runs `airust interactive` with the given lines on stdin and returns stdout
+fn run_interactive(dir: &Path, lines: &[&str]) -> String {
+    run_with_stdin(dir, &["interactive"], lines)
+}
+
+/// Runs airust with the given arguments and lines on stdin and returns stdout
+fn run_with_stdin(dir: &Path, args: &[&str], lines: &[&str]) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_airust"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(format!("{}\n", lines.join("\n")).as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_interactive_reload_picks_up_edited_file() {
+    let dir = temp_workdir("interactive_reload");
+    let kb_path = dir.join("kb.json");
+    fs::write(
+        &kb_path,
+        r#"[{"input": "What is airust?", "output": {"Text": "A Rust library"}}]"#,
+    )
+    .unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_airust"))
+        .args(["interactive", kb_path.to_str().unwrap()])
+        .current_dir(&dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    // Ask before the file knows the answer and wait for the reply
+    stdin.write_all(b"3\nExplain BM25\n").unwrap();
+    let mut before = String::new();
+    while !before.contains("Answer") {
+        assert!(stdout.read_line(&mut before).unwrap() > 0, "{}", before);
+    }
+    assert!(before.contains("No matching answer found."), "{}", before);
+
+    fs::write(
+        &kb_path,
+        r#"[
+            {"input": "What is airust?", "output": {"Text": "A Rust library"}},
+            {"input": "Explain BM25", "output": {"Text": "A ranking function"}}
+        ]"#,
+    )
+    .unwrap();
+    stdin.write_all(b":reload\nExplain BM25\nexit\n").unwrap();
+    drop(stdin);
+
+    let mut after = String::new();
+    stdout.read_to_string(&mut after).unwrap();
+    assert!(child.wait().unwrap().success());
+    assert!(after.contains("Reloaded 2 examples."), "{}", after);
+    assert!(after.contains("): A ranking function"), "{}", after);
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_answers_show_confidence_unless_quiet() {
+    let dir = temp_workdir("confidence");
+    let kb_path = dir.join("kb.json");
+    fs::write(
+        &kb_path,
+        r#"[{"input": "What is airust?", "output": {"Text": "A Rust library"}}]"#,
+    )
+    .unwrap();
+    let kb = kb_path.to_str().unwrap();
+
+    let stdout = run_with_stdin(
+        &dir,
+        &["interactive", kb],
+        &["1", "What is airust?", "exit"],
+    );
+    assert!(
+        stdout.contains("Answer (confidence 1.00): A Rust library"),
+        "{}",
+        stdout
+    );
+
+    let stdout = run_with_stdin(
+        &dir,
+        &["interactive", kb, "--quiet"],
+        &["1", "What is airust?", "exit"],
+    );
+    assert!(stdout.contains("Answer: A Rust library"), "{}", stdout);
+    assert!(!stdout.contains("confidence"));
+
+    // Testing a loaded knowledge base follows the same switch
+    let knowledge_test = ["2", kb, "1", "1", "What is airust?", "exit"];
+    let stdout = run_with_stdin(&dir, &["knowledge"], &knowledge_test);
+    assert!(
+        stdout.contains("Answer (confidence 1.00): A Rust library"),
+        "{}",
+        stdout
+    );
+    let stdout = run_with_stdin(&dir, &["knowledge", "--quiet"], &knowledge_test);
+    assert!(stdout.contains("Answer: A Rust library"), "{}", stdout);
+    assert!(!stdout.contains("confidence"));
+
+    // One-off queries use the embedded data, which may be empty
+    let output = Command::new(env!("CARGO_BIN_EXE_airust"))
+        .args(["query", "simple", "What is airust?"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Answer (confidence "));
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_interactive_context_format_selection() {
+    let dir = temp_workdir("context_format");
+
+    // Context agent with the list format
+    let stdout = run_interactive(
+        &dir,
+        &[
+            "4",
+            "2",
+            "What is airust?",
+            ":context Who wrote it?",
+            "exit",
+        ],
+    );
+    assert!(stdout.contains("Context format: List"), "{}", stdout);
+    assert!(
+        stdout.contains("Enhanced input: Who wrote it? [Context: [What is airust? -> "),
+        "{}",
+        stdout
+    );
+
+    // Pressing enter keeps the default
+    let stdout = run_interactive(&dir, &["4", "", "What is airust?", "exit"]);
+    assert!(stdout.contains("Context format: QAPairs"), "{}", stdout);
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_knowledge_dump_writes_embedded_data() {
+    let dir = temp_workdir("knowledge_dump");
+    let dump = dir.join("defaults.json");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_airust"))
+        .args(["knowledge", "dump", dump.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&dump).unwrap()).unwrap();
+    assert!(saved["version"].is_string());
+    assert!(saved["examples"].is_array());
+
+    fs::remove_dir_all(dir).ok();
+}